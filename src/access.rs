@@ -0,0 +1,122 @@
+use std::{error::Error, fs::File};
+use csv::{ReaderBuilder, Writer};
+use rayon::prelude::*;
+use serde::{Serialize, Deserialize};
+use crate::graph::{Graph, GTFSGraph};
+use crate::snap::EdgeSnapper;
+
+//where a stop meets the walking network
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct StopLink {
+    pub stop_id: String,
+    pub edge_id: String,
+    //metres along the edge from its source node
+    pub offset: f64,
+    //metres walked from the stop to the street
+    pub distance: f64,
+    pub lon: f64,
+    pub lat: f64,
+}
+
+//stops with no foot edge in reach, written out for QA
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct UnlinkedStop {
+    pub stop_id: String,
+    pub name: String,
+    pub lon: f64,
+    pub lat: f64,
+}
+
+#[derive(Debug, Default)]
+pub struct StopAccess {
+    pub links: Vec<StopLink>,
+    pub unlinked: Vec<UnlinkedStop>,
+}
+
+impl StopAccess {
+    //snaps every stop onto the nearest foot-allowed edge within max_distance metres
+    pub fn link(gtfs: &GTFSGraph, graph: &Graph, max_distance: f64) -> Self {
+        let snapper = EdgeSnapper::new(graph);
+        let results: Vec<Result<StopLink, UnlinkedStop>> = gtfs.stops.par_iter().map(|stop| {
+            match snapper.snap(graph, stop.lon, stop.lat, max_distance, |edge| edge.foot) {
+                Some(snap) => Ok(StopLink {
+                    stop_id: stop.id.clone(),
+                    edge_id: graph.edges[snap.edge].id.clone(),
+                    offset: snap.offset,
+                    distance: snap.distance,
+                    lon: snap.lon,
+                    lat: snap.lat,
+                }),
+                None => Err(UnlinkedStop {
+                    stop_id: stop.id.clone(),
+                    name: gtfs.stop_names.get(&stop.id).cloned().unwrap_or_default(),
+                    lon: stop.lon,
+                    lat: stop.lat,
+                }),
+            }
+        }).collect();
+
+        let mut access = Self::default();
+        for result in results {
+            match result {
+                Ok(link) => access.links.push(link),
+                Err(stop) => access.unlinked.push(stop),
+            }
+        }
+        access
+    }
+
+    pub fn get(&self, stop_id: &str) -> Option<&StopLink> {
+        self.links.iter().find(|link| link.stop_id == stop_id)
+    }
+
+    pub fn to_csv(&self, file_path: &str) -> Result<(), Box<dyn Error>> {
+        let mut wtr = Writer::from_path(file_path)?;
+        for link in &self.links {
+            wtr.serialize(link)?;
+        }
+        wtr.flush()?;
+        Ok(())
+    }
+
+    pub fn unlinked_to_csv(&self, file_path: &str) -> Result<(), Box<dyn Error>> {
+        let mut wtr = Writer::from_path(file_path)?;
+        for stop in &self.unlinked {
+            wtr.serialize(stop)?;
+        }
+        wtr.flush()?;
+        Ok(())
+    }
+
+    pub fn from_csv(file_path: &str) -> Result<Self, Box<dyn Error>> {
+        let file = File::open(file_path)?;
+        let mut rdr = ReaderBuilder::new().from_reader(file);
+        let mut access = Self::default();
+        for result in rdr.deserialize::<StopLink>() {
+            access.links.push(result?);
+        }
+        Ok(access)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::StopAccess;
+    use crate::graph::{Graph, GTFSGraph};
+
+    #[test]
+    fn test_link_stops() {
+        let graph = Graph::from_csv("testedges.csv", "testnodes.csv");
+        let mut gtfs = GTFSGraph::new("test");
+        gtfs.add_stop("near".to_string(), "Near".to_string(), Some(33.4838), Some(-119.0343));
+        gtfs.add_stop("far".to_string(), "Far".to_string(), Some(34.05), Some(-118.25));
+        let access = StopAccess::link(&gtfs, &graph, 100.0);
+        assert_eq!(access.links.len(), 1);
+        let link = access.get("near").unwrap();
+        assert!(link.distance < 100.0);
+        assert!(graph.edges.iter().any(|edge| edge.id == link.edge_id && edge.foot));
+        assert_eq!(access.unlinked.len(), 1);
+        assert_eq!(access.unlinked[0].stop_id, "far");
+        assert_eq!(access.unlinked[0].name, "Far");
+    }
+}
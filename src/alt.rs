@@ -0,0 +1,258 @@
+use std::{collections::BinaryHeap, error::Error, fs::File, io::{BufReader, BufWriter}, time::Instant};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use serde::{Serialize, Deserialize};
+use crate::interner::NodeInterner;
+use crate::routing::{Arc, Profile, Route, Router, SearchStats, State};
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LandmarkSelection {
+    //each landmark as far as possible from the ones already picked
+    Farthest,
+    //Goldberg and Werneck: the leaf under the shortest path tree branch the current landmarks bound worst
+    Avoid,
+}
+
+//arc weight the landmark tables are computed over
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LandmarkMetric {
+    //metres
+    Length,
+    //seconds
+    Duration,
+}
+
+impl LandmarkMetric {
+    pub fn weight(&self, arc: &Arc) -> f64 {
+        match self {
+            LandmarkMetric::Length => arc.length,
+            LandmarkMetric::Duration => arc.duration,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Landmarks {
+    pub profile: Profile,
+    pub metric: LandmarkMetric,
+    //same numbering as the Router it was built from
+    pub nodes: NodeInterner,
+    //dense node indices
+    pub landmarks: Vec<usize>,
+    //from[landmark][node], metric distance from the landmark to the node
+    pub from: Vec<Vec<f64>>,
+    //to[landmark][node], metric distance from the node to the landmark
+    pub to: Vec<Vec<f64>>,
+}
+
+impl Landmarks {
+    pub fn new(router: &Router, count: usize, selection: LandmarkSelection, metric: LandmarkMetric) -> Self {
+        let reversed = router.reversed();
+        let mut landmarks = Self {
+            profile: router.profile,
+            metric,
+            nodes: router.nodes.clone(),
+            landmarks: Vec::new(),
+            from: Vec::new(),
+            to: Vec::new(),
+        };
+        let mut rng = StdRng::seed_from_u64(0);
+        for _ in 0..count.min(router.node_count()) {
+            let picked = match selection {
+                LandmarkSelection::Avoid if !landmarks.landmarks.is_empty() => {
+                    let root = rng.gen_range(0..router.node_count());
+                    landmarks.avoid(router, root).or_else(|| landmarks.farthest(router))
+                }
+                _ => landmarks.farthest(router),
+            };
+            let Some(landmark) = picked else {
+                break;
+            };
+            landmarks.landmarks.push(landmark);
+            landmarks.from.push(router.dijkstra(&[(landmark, 0.0)], None, f64::INFINITY, |arc| metric.weight(arc)).cost);
+            landmarks.to.push(reversed.dijkstra(&[(landmark, 0.0)], None, f64::INFINITY, |arc| metric.weight(arc)).cost);
+        }
+        landmarks
+    }
+
+    // the node farthest from its nearest landmark, either way round, the first landmark is the
+    // node farthest from node 0
+    fn farthest(&self, router: &Router) -> Option<usize> {
+        let distance: Vec<f64> = if self.landmarks.is_empty() {
+            router.dijkstra(&[(0, 0.0)], None, f64::INFINITY, |arc| self.metric.weight(arc)).cost
+        } else {
+            (0..router.node_count())
+                .map(|node| {
+                    self.from.iter().chain(&self.to)
+                        .map(|costs| costs[node])
+                        .filter(|cost| cost.is_finite())
+                        .fold(f64::INFINITY, f64::min)
+                })
+                .collect()
+        };
+        (0..router.node_count())
+            .filter(|node| distance[*node].is_finite() && !self.landmarks.contains(node))
+            .max_by(|a, b| distance[*a].total_cmp(&distance[*b]))
+    }
+
+    fn avoid(&self, router: &Router, root: usize) -> Option<usize> {
+        let search = router.dijkstra(&[(root, 0.0)], None, f64::INFINITY, |arc| self.metric.weight(arc));
+        let mut reached: Vec<usize> = (0..router.node_count()).filter(|node| search.reached(*node)).collect();
+        reached.sort_by(|a, b| search.cost[*b].total_cmp(&search.cost[*a]));
+
+        // weight is how much the current landmarks underestimate the distance from the root,
+        // summed over each subtree; subtrees holding a landmark are already covered
+        let mut size: Vec<f64> = (0..router.node_count()).map(|node| search.cost[node] - self.bound(root, node)).collect();
+        let mut covered: Vec<bool> = (0..router.node_count()).map(|node| self.landmarks.contains(&node)).collect();
+        let mut children: Vec<Vec<usize>> = vec![Vec::new(); router.node_count()];
+        for node in &reached {
+            if let Some((tail, _)) = search.parent[*node] {
+                children[tail].push(*node);
+                size[tail] += size[*node];
+                covered[tail] |= covered[*node];
+            }
+        }
+        for node in &reached {
+            if covered[*node] {
+                size[*node] = 0.0;
+            }
+        }
+
+        let mut node = reached.iter().copied().filter(|node| size[*node] > 0.0).max_by(|a, b| size[*a].total_cmp(&size[*b]))?;
+        while let Some(child) = children[node].iter().copied().max_by(|a, b| size[*a].total_cmp(&size[*b])) {
+            node = child;
+        }
+        Some(node)
+    }
+
+    //lower bound on the metric distance from source to target by the triangle inequality
+    pub fn bound(&self, source: usize, target: usize) -> f64 {
+        let mut best: f64 = 0.0;
+        for (from, to) in self.from.iter().zip(&self.to) {
+            let ahead = from[target] - from[source];
+            if ahead.is_finite() {
+                best = best.max(ahead);
+            }
+            let behind = to[source] - to[target];
+            if behind.is_finite() {
+                best = best.max(behind);
+            }
+        }
+        best
+    }
+
+    pub fn save(&self, file_path: &str) -> Result<(), Box<dyn Error>> {
+        let file = File::create(file_path)?;
+        serde_json::to_writer(BufWriter::new(file), self)?;
+        Ok(())
+    }
+
+    pub fn load(file_path: &str) -> Result<Self, Box<dyn Error>> {
+        let file = File::open(file_path)?;
+        Ok(serde_json::from_reader(BufReader::new(file))?)
+    }
+
+    //A* guided by the landmarks under any cost with cost(arc) >= scale * metric weight of arc,
+    //e.g. durations with a Duration metric and scale 1, or durations with a Length metric and
+    //scale 1 / the profile's max speed
+    pub fn query<F>(&self, router: &Router, source: usize, target: usize, scale: f64, cost: F) -> (Option<Route>, SearchStats) where F: Fn(&Arc) -> f64 {
+        let start_time = Instant::now();
+        let heuristic = |node: usize| scale * self.bound(node, target);
+        let mut costs = vec![f64::INFINITY; router.node_count()];
+        let mut parent: Vec<Option<(usize, Arc)>> = vec![None; router.node_count()];
+        let mut settled = vec![false; router.node_count()];
+        let mut stats = SearchStats::default();
+        let mut heap = BinaryHeap::new();
+
+        costs[source] = 0.0;
+        heap.push(State { cost: heuristic(source), node: source });
+        while let Some(State { node, .. }) = heap.pop() {
+            // landmark bounds are consistent, so the first pop of a node is final
+            if settled[node] {
+                continue;
+            }
+            settled[node] = true;
+            stats.settled += 1;
+            if node == target {
+                break;
+            }
+            for arc in &router.arcs[node] {
+                let next = costs[node] + cost(arc);
+                if next < costs[arc.head] {
+                    costs[arc.head] = next;
+                    parent[arc.head] = Some((node, *arc));
+                    heap.push(State { cost: next + heuristic(arc.head), node: arc.head });
+                }
+            }
+        }
+
+        let route = if settled[target] {
+            let mut path = Vec::new();
+            let mut current = target;
+            while let Some((tail, arc)) = parent[current] {
+                path.push(arc);
+                current = tail;
+            }
+            path.reverse();
+            Some(Router::to_route(path))
+        } else {
+            None
+        };
+        stats.elapsed = start_time.elapsed();
+        (route, stats)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{LandmarkMetric, LandmarkSelection, Landmarks};
+    use crate::graph::fixtures::grid;
+    use crate::routing::{Arc, Profile, Router};
+    use approx::assert_relative_eq;
+
+    #[test]
+    fn test_alt_matches_dijkstra() {
+        let graph = grid();
+        let router = Router::new(&graph, Profile::Car);
+        for selection in [LandmarkSelection::Farthest, LandmarkSelection::Avoid] {
+            let landmarks = Landmarks::new(&router, 4, selection, LandmarkMetric::Duration);
+            assert_eq!(landmarks.landmarks.len(), 4);
+            // durations doubled off the primary road, still at least the metric the tables hold
+            let factor = |edge: usize| if graph.edges[edge].car_forward == "Primary" { 1.0 } else { 2.0 };
+            let custom = |arc: &Arc| factor(arc.edge) * arc.duration;
+            for source in 0..router.node_count() {
+                for target in 0..router.node_count() {
+                    let (expected, dijkstra_stats) = router.shortest_path_with_stats(source, target);
+                    let (found, alt_stats) = landmarks.query(&router, source, target, 1.0, |arc| arc.duration);
+                    assert_eq!(expected.is_some(), found.is_some());
+                    if let (Some(expected), Some(found)) = (expected, found) {
+                        assert_relative_eq!(expected.duration, found.duration, epsilon = 1e-6);
+                        assert!(alt_stats.settled <= dijkstra_stats.settled);
+                    }
+
+                    let search = router.dijkstra(&[(source, 0.0)], Some(target), f64::INFINITY, custom);
+                    let (found, _) = landmarks.query(&router, source, target, 1.0, custom);
+                    assert_eq!(search.reached(target), found.is_some());
+                    if let Some(found) = found {
+                        let cost: f64 = found.edges.iter().map(|(edge, forward)| {
+                            factor(*edge) * graph.edges[*edge].length / Profile::Car.speed(&graph.edges[*edge], *forward).unwrap()
+                        }).sum();
+                        assert_relative_eq!(search.cost[target], cost, epsilon = 1e-6);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_landmarks_save_and_load() {
+        let graph = grid();
+        let router = Router::new(&graph, Profile::Foot);
+        let landmarks = Landmarks::new(&router, 3, LandmarkSelection::Avoid, LandmarkMetric::Length);
+        let file_path = std::env::temp_dir().join("algo_test_landmarks.json");
+        landmarks.save(file_path.to_str().unwrap()).unwrap();
+        let loaded = Landmarks::load(file_path.to_str().unwrap()).unwrap();
+        assert_eq!(loaded.landmarks, landmarks.landmarks);
+        let (route, _) = loaded.query(&router, 0, 35, 1.0 / Profile::Foot.max_speed(), |arc| arc.duration);
+        assert_relative_eq!(route.unwrap().duration, router.shortest_path(0, 35).unwrap().duration, epsilon = 1e-6);
+    }
+}
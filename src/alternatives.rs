@@ -0,0 +1,125 @@
+use std::collections::{HashMap, HashSet};
+use crate::graph::{Graph, Node};
+use crate::routing::{Route, Router};
+
+#[derive(Debug, Clone, Copy)]
+pub struct AlternativeOptions {
+    //routes to return, the best route included
+    pub count: usize,
+    //longest duration allowed, as a multiple of the best route's
+    pub max_stretch: f64,
+    //largest share of a route's length allowed to overlap any route already picked
+    pub max_overlap: f64,
+    //cost factor applied to every edge of a found route before searching again
+    pub penalty: f64,
+    pub max_iterations: usize,
+}
+
+impl Default for AlternativeOptions {
+    fn default() -> Self {
+        Self {
+            count: 3,
+            max_stretch: 1.4,
+            max_overlap: 0.6,
+            penalty: 1.5,
+            max_iterations: 10,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Alternative {
+    pub route: Route,
+    pub geometry: Vec<Node>,
+    //share of this route's length that is also on the best route
+    pub shared_with_best: f64,
+}
+
+//share of route's length on edges of other
+fn overlap(graph: &Graph, route: &Route, other: &Route) -> f64 {
+    let edges: HashSet<usize> = other.edges.iter().map(|(edge, _)| *edge).collect();
+    let (mut shared, mut total) = (0.0, 0.0);
+    for (edge, _) in &route.edges {
+        let length = graph.edges[*edge].length;
+        total += length;
+        if edges.contains(edge) {
+            shared += length;
+        }
+    }
+    if total > 0.0 { shared / total } else { 1.0 }
+}
+
+//penalty method: repeatedly search with the edges of earlier results made more expensive
+pub fn alternatives(graph: &Graph, router: &Router, source: usize, target: usize, options: &AlternativeOptions) -> Vec<Alternative> {
+    let Some(best) = router.shortest_path(source, target) else {
+        return Vec::new();
+    };
+    let mut found: Vec<Route> = vec![best.clone()];
+    let mut penalties: HashMap<usize, f64> = HashMap::new();
+    let mut last = best.clone();
+    for _ in 0..options.max_iterations {
+        if found.len() >= options.count {
+            break;
+        }
+        for (edge, _) in &last.edges {
+            *penalties.entry(*edge).or_insert(1.0) *= options.penalty;
+        }
+        let search = router.dijkstra(&[(source, 0.0)], Some(target), f64::INFINITY, |arc| arc.duration * penalties.get(&arc.edge).copied().unwrap_or(1.0));
+        if !search.reached(target) {
+            break;
+        }
+        let candidate = Router::to_route(search.path_to(target));
+        last = candidate.clone();
+        if candidate.duration > best.duration * options.max_stretch {
+            continue;
+        }
+        if found.iter().any(|route| route.edges == candidate.edges || overlap(graph, &candidate, route) > options.max_overlap) {
+            continue;
+        }
+        found.push(candidate);
+    }
+
+    found.into_iter().map(|route| Alternative {
+        shared_with_best: overlap(graph, &route, &best),
+        geometry: route.geometry(graph),
+        route,
+    }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{alternatives, AlternativeOptions};
+    use crate::graph::fixtures::paths;
+    use crate::graph::{Graph, Node};
+    use crate::routing::{Profile, Router};
+    use approx::assert_relative_eq;
+
+    // three disjoint ways from 1 to 4: via 2 (best), via 3 (a little longer) and via 5 (far too long)
+    fn ladder() -> Graph {
+        let nodes = [
+            Node::new(1, -118.0, 34.0),
+            Node::new(2, -118.001, 34.001),
+            Node::new(3, -117.999, 34.001),
+            Node::new(4, -118.0, 34.002),
+            Node::new(5, -117.99, 34.001),
+        ];
+        paths(&nodes, &[("12", 0, 1, 100.0), ("24", 1, 3, 100.0), ("13", 0, 2, 110.0), ("34", 2, 3, 110.0), ("15", 0, 4, 400.0), ("54", 4, 3, 400.0)])
+    }
+
+    #[test]
+    fn test_alternatives_within_stretch() {
+        let graph = ladder();
+        let router = Router::new(&graph, Profile::Foot);
+        let (source, target) = (router.node_index(1).unwrap(), router.node_index(4).unwrap());
+        let routes = alternatives(&graph, &router, source, target, &AlternativeOptions::default());
+        assert_eq!(routes.len(), 2);
+        assert_eq!(routes[0].route.edge_ids(&graph), vec!["12", "24"]);
+        assert_relative_eq!(routes[0].shared_with_best, 1.0);
+        assert_eq!(routes[1].route.edge_ids(&graph), vec!["13", "34"]);
+        assert_relative_eq!(routes[1].shared_with_best, 0.0);
+        assert_eq!(routes[1].geometry.len(), 3);
+
+        let loose = AlternativeOptions { max_stretch: 5.0, ..AlternativeOptions::default() };
+        assert_eq!(alternatives(&graph, &router, source, target, &loose).len(), 3);
+    }
+}
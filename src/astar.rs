@@ -0,0 +1,104 @@
+use std::{collections::BinaryHeap, time::Instant};
+use geographiclib_rs::{Geodesic, InverseGeodesic};
+use crate::routing::{Arc, Route, Router, SearchStats, State};
+
+// edges.csv lengths are haversine on a sphere, which can come out a little under the
+// ellipsoidal distance, so the bound is shrunk to stay admissible
+const HEURISTIC_SLACK: f64 = 0.99;
+
+//lower bound on the remaining travel time: straight line distance at the profile's top speed
+pub struct GeodesicHeuristic {
+    geod: Geodesic,
+    target: (f64, f64),
+    max_speed: f64,
+}
+
+impl GeodesicHeuristic {
+    pub fn new(router: &Router, target: usize) -> Self {
+        Self {
+            geod: Geodesic::wgs84(),
+            target: router.coords[target],
+            max_speed: router.profile.max_speed(),
+        }
+    }
+
+    pub fn estimate(&self, coord: (f64, f64)) -> f64 {
+        let distance: f64 = self.geod.inverse(coord.1, coord.0, self.target.1, self.target.0);
+        HEURISTIC_SLACK * distance / self.max_speed
+    }
+}
+
+//point to point A* over the router's durations
+pub fn astar(router: &Router, source: usize, target: usize) -> (Option<Route>, SearchStats) {
+    let start_time = Instant::now();
+    let heuristic = GeodesicHeuristic::new(router, target);
+    let mut cost = vec![f64::INFINITY; router.node_count()];
+    let mut parent: Vec<Option<(usize, Arc)>> = vec![None; router.node_count()];
+    let mut settled = vec![false; router.node_count()];
+    let mut stats = SearchStats::default();
+    let mut heap = BinaryHeap::new();
+
+    cost[source] = 0.0;
+    heap.push(State { cost: heuristic.estimate(router.coords[source]), node: source });
+    while let Some(State { node, .. }) = heap.pop() {
+        // the heuristic is consistent, so the first pop of a node is final
+        if settled[node] {
+            continue;
+        }
+        settled[node] = true;
+        stats.settled += 1;
+        if node == target {
+            break;
+        }
+        for arc in &router.arcs[node] {
+            let next = cost[node] + arc.duration;
+            if next < cost[arc.head] {
+                cost[arc.head] = next;
+                parent[arc.head] = Some((node, *arc));
+                heap.push(State { cost: next + heuristic.estimate(router.coords[arc.head]), node: arc.head });
+            }
+        }
+    }
+
+    let route = if settled[target] {
+        let mut path = Vec::new();
+        let mut current = target;
+        while let Some((tail, arc)) = parent[current] {
+            path.push(arc);
+            current = tail;
+        }
+        path.reverse();
+        Some(Router::to_route(path))
+    } else {
+        None
+    };
+    stats.elapsed = start_time.elapsed();
+    (route, stats)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::astar;
+    use crate::graph::Graph;
+    use crate::routing::{Profile, Router};
+    use approx::assert_relative_eq;
+
+    #[test]
+    fn test_astar_matches_dijkstra() {
+        let graph = Graph::from_csv("testedges.csv", "testnodes.csv");
+        for profile in [Profile::Foot, Profile::Bike] {
+            let router = Router::new(&graph, profile);
+            for source in 0..router.node_count() {
+                for target in 0..router.node_count() {
+                    let (expected, dijkstra_stats) = router.shortest_path_with_stats(source, target);
+                    let (found, astar_stats) = astar(&router, source, target);
+                    assert_eq!(expected.is_some(), found.is_some());
+                    if let (Some(expected), Some(found)) = (expected, found) {
+                        assert_relative_eq!(expected.duration, found.duration, epsilon = 1e-6);
+                        assert!(astar_stats.settled <= dijkstra_stats.settled);
+                    }
+                }
+            }
+        }
+    }
+}
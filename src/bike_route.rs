@@ -0,0 +1,54 @@
+use std::{collections::HashMap, time::Instant};
+mod graph;
+mod calendar;
+mod transfers;
+mod geodesic;
+mod interner;
+mod routing;
+mod elevation;
+use graph::Graph;
+use routing::{Profile, Router};
+use elevation::{read_elevations, read_surfaces, BikeCostModel, BikeWeights};
+
+fn main() {
+    let args = arguments::parse(std::env::args()).unwrap();
+    let edges = args.get::<String>("edges").unwrap_or_else(|| "testedges.csv".to_string());
+    let nodes = args.get::<String>("nodes").unwrap_or_else(|| "testnodes.csv".to_string());
+    let source = args.get::<u64>("from").unwrap_or(2729443585);
+    let target = args.get::<u64>("to").unwrap_or(2729463686);
+    let defaults = BikeWeights::default();
+    let weights = BikeWeights {
+        distance: args.get::<f64>("distance-weight").unwrap_or(defaults.distance),
+        climb: args.get::<f64>("climb-weight").unwrap_or(defaults.climb),
+        surface: args.get::<f64>("surface-weight").unwrap_or(defaults.surface),
+    };
+    let elevations = match args.get::<String>("elevations") {
+        Some(file) => read_elevations(&file).unwrap(),
+        None => HashMap::new(),
+    };
+    let surfaces = match args.get::<String>("surfaces") {
+        Some(file) => read_surfaces(&file).unwrap(),
+        None => HashMap::new(),
+    };
+
+    let start_time = Instant::now();
+    let graph = Graph::from_csv(&edges, &nodes);
+    let router = Router::new(&graph, Profile::Bike);
+    let model = BikeCostModel::new(&graph, &elevations, &surfaces, weights);
+    eprintln!("loading took {:?}", start_time.elapsed().as_secs_f64());
+
+    let (Some(source), Some(target)) = (router.node_index(source), router.node_index(target)) else {
+        println!("Unknown node");
+        return;
+    };
+    match model.route(&router, source, target) {
+        Some(best) => println!("weighted: distance: {:.1} m, ascent: {:.1} m, cost: {:.1}", best.route.distance, best.ascent, best.cost),
+        None => println!("No path found"),
+    }
+    let start_time = Instant::now();
+    let pareto = model.pareto_routes(&router, source, target);
+    eprintln!("pareto search took {:?}", start_time.elapsed().as_secs_f64());
+    for route in pareto {
+        println!("pareto: distance: {:.1} m, ascent: {:.1} m", route.route.distance, route.ascent);
+    }
+}
@@ -0,0 +1,146 @@
+use std::{collections::{HashMap, HashSet}, error::Error};
+use chrono::{Datelike, DateTime, Duration, FixedOffset, NaiveDate, Offset, Utc};
+use chrono_tz::Tz;
+use gtfs_structures::{Exception, Gtfs};
+use serde::{Serialize, Deserialize};
+
+//calendar.txt row, weekdays from monday
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ServicePattern {
+    pub weekdays: [bool; 7],
+    pub start_date: NaiveDate,
+    //inclusive
+    pub end_date: NaiveDate,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct Calendar {
+    //iana name from agency.txt, e.g. America/Los_Angeles
+    pub timezone: String,
+    //<service id, weekly pattern>
+    pub services: HashMap<String, ServicePattern>,
+    //<service id, <date, true if added, false if removed>> from calendar_dates.txt
+    pub exceptions: HashMap<String, HashMap<NaiveDate, bool>>,
+}
+
+impl Calendar {
+    pub fn new(timezone: &str) -> Self {
+        Self {
+            timezone: timezone.to_string(),
+            services: HashMap::new(),
+            exceptions: HashMap::new(),
+        }
+    }
+
+    pub fn from_gtfs(gtfs: &Gtfs) -> Self {
+        let timezone = gtfs.agencies.first().map(|agency| agency.timezone.as_str()).unwrap_or("UTC");
+        let mut calendar = Calendar::new(timezone);
+        for service in gtfs.calendar.values() {
+            let weekdays = [service.monday, service.tuesday, service.wednesday, service.thursday, service.friday, service.saturday, service.sunday];
+            calendar.add_service(service.id.clone(), weekdays, service.start_date, service.end_date);
+        }
+        for dates in gtfs.calendar_dates.values() {
+            for date in dates {
+                calendar.add_exception(date.service_id.clone(), date.date, date.exception_type == Exception::Added);
+            }
+        }
+        calendar
+    }
+
+    pub fn add_service(&mut self, id: String, weekdays: [bool; 7], start_date: NaiveDate, end_date: NaiveDate) {
+        self.services.insert(id, ServicePattern { weekdays, start_date, end_date });
+    }
+
+    pub fn add_exception(&mut self, id: String, date: NaiveDate, added: bool) {
+        self.exceptions.entry(id).or_default().insert(date, added);
+    }
+
+    //calendar_dates.txt wins over the weekly pattern, services only listed there run just on their added dates
+    pub fn runs(&self, service_id: &str, date: NaiveDate) -> bool {
+        if let Some(added) = self.exceptions.get(service_id).and_then(|dates| dates.get(&date)) {
+            return *added;
+        }
+        match self.services.get(service_id) {
+            Some(pattern) => pattern.start_date <= date && date <= pattern.end_date && pattern.weekdays[date.weekday().num_days_from_monday() as usize],
+            None => false,
+        }
+    }
+
+    pub fn services_on(&self, date: NaiveDate) -> HashSet<String> {
+        self.services.keys().chain(self.exceptions.keys())
+            .filter(|service_id| self.runs(service_id, date))
+            .cloned()
+            .collect()
+    }
+
+    //services running on any day from start to end inclusive
+    pub fn services_between(&self, start: NaiveDate, end: NaiveDate) -> HashSet<String> {
+        let mut services = HashSet::new();
+        let mut date = start;
+        while date <= end {
+            services.extend(self.services_on(date));
+            date += Duration::days(1);
+        }
+        services
+    }
+
+    //the date it is now where the agency is, not where this machine is
+    pub fn today(&self) -> Result<NaiveDate, Box<dyn Error>> {
+        self.local_date(Utc::now())
+    }
+
+    pub fn local_date(&self, at: DateTime<Utc>) -> Result<NaiveDate, Box<dyn Error>> {
+        let offset = utc_offset(&self.timezone, at)?;
+        Ok(at.with_timezone(&offset).date_naive())
+    }
+}
+
+//utc offset of an iana timezone at an instant, from the tz database built in, a name it doesn't know is an error
+pub fn utc_offset(timezone: &str, at: DateTime<Utc>) -> Result<FixedOffset, Box<dyn Error>> {
+    let zone: Tz = timezone.parse().map_err(|error| format!("unknown timezone {}: {}", timezone, error))?;
+    Ok(at.with_timezone(&zone).offset().fix())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{utc_offset, Calendar};
+    use chrono::{NaiveDate, TimeZone, Utc};
+
+    fn date(y: i32, m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(y, m, d).unwrap()
+    }
+
+    #[test]
+    fn test_weekdays_ranges_and_exceptions() {
+        let mut calendar = Calendar::new("America/Los_Angeles");
+        let weekdays = [true, true, true, true, true, false, false];
+        calendar.add_service("weekday".to_string(), weekdays, date(2024, 1, 15), date(2024, 1, 26));
+        calendar.add_service("sunday".to_string(), [false, false, false, false, false, false, true], date(2024, 1, 14), date(2024, 1, 21));
+        calendar.add_exception("weekday".to_string(), date(2024, 1, 16), false);
+        calendar.add_exception("special".to_string(), date(2024, 1, 20), true);
+
+        assert!(calendar.runs("weekday", date(2024, 1, 15)));
+        // removed in calendar_dates
+        assert!(!calendar.runs("weekday", date(2024, 1, 16)));
+        // saturday, then outside the range
+        assert!(!calendar.runs("weekday", date(2024, 1, 20)));
+        assert!(!calendar.runs("weekday", date(2024, 1, 29)));
+        assert!(!calendar.runs("sunday", date(2024, 1, 28)));
+        assert!(calendar.services_on(date(2024, 1, 20)).into_iter().collect::<Vec<_>>() == vec!["special".to_string()]);
+        assert_eq!(calendar.services_between(date(2024, 1, 19), date(2024, 1, 21)).len(), 3);
+        assert!(calendar.services_on(date(2024, 1, 16)).is_empty());
+    }
+
+    #[test]
+    fn test_agency_date() {
+        let calendar = Calendar::new("America/Los_Angeles");
+        let offset = utc_offset("America/Los_Angeles", Utc.with_ymd_and_hms(2024, 1, 16, 0, 0, 0).unwrap()).unwrap();
+        assert_eq!(offset.local_minus_utc(), -8 * 3600);
+        // 5am utc is still the previous evening in los angeles, in winter and summer, and past 2037 too
+        assert_eq!(calendar.local_date(Utc.with_ymd_and_hms(2024, 1, 16, 5, 0, 0).unwrap()).unwrap(), date(2024, 1, 15));
+        assert_eq!(calendar.local_date(Utc.with_ymd_and_hms(2024, 7, 16, 6, 59, 0).unwrap()).unwrap(), date(2024, 7, 15));
+        assert_eq!(calendar.local_date(Utc.with_ymd_and_hms(2024, 7, 16, 7, 0, 0).unwrap()).unwrap(), date(2024, 7, 16));
+        assert_eq!(calendar.local_date(Utc.with_ymd_and_hms(2040, 7, 16, 6, 59, 0).unwrap()).unwrap(), date(2040, 7, 15));
+        assert!(Calendar::new("America/Long_Beach").today().is_err());
+    }
+}
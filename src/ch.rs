@@ -0,0 +1,381 @@
+use std::{collections::{BinaryHeap, HashMap}, cmp::Reverse, error::Error, fs::File, io::{BufReader, BufWriter}};
+use serde::{Serialize, Deserialize};
+use crate::interner::NodeInterner;
+use crate::routing::{Profile, Route, Router, State};
+
+// witness searches give up after settling this many nodes and keep the shortcut instead
+const WITNESS_SETTLE_LIMIT: usize = 500;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum ChArcKind {
+    //(index into Graph::edges, travelled forward)
+    Original(usize, bool),
+    //the two arcs, as indices into ContractionHierarchy::arcs, that the shortcut skips over
+    Shortcut(usize, usize),
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct ChArc {
+    pub tail: usize,
+    pub head: usize,
+    //seconds
+    pub cost: f64,
+    //metres
+    pub length: f64,
+    pub kind: ChArcKind,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ContractionHierarchy {
+    pub profile: Profile,
+    //same numbering as the Router it was built from
+    pub nodes: NodeInterner,
+    pub rank: Vec<usize>,
+    pub arcs: Vec<ChArc>,
+    //arcs leaving each node towards a higher rank
+    pub up: Vec<Vec<usize>>,
+    //arcs entering each node from a higher rank
+    pub down: Vec<Vec<usize>>,
+}
+
+//the shrinking graph of not yet contracted nodes
+struct Contractor {
+    arcs: Vec<ChArc>,
+    //<tail, <head, arc>>
+    out: Vec<HashMap<usize, usize>>,
+    //<head, <tail, arc>>
+    inc: Vec<HashMap<usize, usize>>,
+    deleted_neighbours: Vec<i64>,
+    dist: Vec<f64>,
+    touched: Vec<usize>,
+}
+
+impl Contractor {
+    fn new(router: &Router) -> Self {
+        let n = router.node_count();
+        let mut contractor = Self {
+            arcs: Vec::new(),
+            out: vec![HashMap::new(); n],
+            inc: vec![HashMap::new(); n],
+            deleted_neighbours: vec![0; n],
+            dist: vec![f64::INFINITY; n],
+            touched: Vec::new(),
+        };
+        for (tail, arcs) in router.arcs.iter().enumerate() {
+            for arc in arcs {
+                if arc.head != tail {
+                    contractor.add_arc(ChArc { tail, head: arc.head, cost: arc.duration, length: arc.length, kind: ChArcKind::Original(arc.edge, arc.forward) });
+                }
+            }
+        }
+        contractor
+    }
+
+    //keeps only the cheapest arc between any two nodes
+    fn add_arc(&mut self, arc: ChArc) {
+        if let Some(existing) = self.out[arc.tail].get(&arc.head) {
+            if self.arcs[*existing].cost <= arc.cost {
+                return;
+            }
+        }
+        let index = self.arcs.len();
+        self.arcs.push(arc);
+        self.out[arc.tail].insert(arc.head, index);
+        self.inc[arc.head].insert(arc.tail, index);
+    }
+
+    //bounded dijkstra from source that never passes through skip, leaves costs in self.dist
+    fn witness_search(&mut self, source: usize, skip: usize, limit: f64) {
+        for node in self.touched.drain(..) {
+            self.dist[node] = f64::INFINITY;
+        }
+        let mut heap = BinaryHeap::new();
+        let mut settled = 0;
+        self.dist[source] = 0.0;
+        self.touched.push(source);
+        heap.push(State { cost: 0.0, node: source });
+        while let Some(State { cost, node }) = heap.pop() {
+            if cost > self.dist[node] {
+                continue;
+            }
+            settled += 1;
+            if cost > limit || settled > WITNESS_SETTLE_LIMIT {
+                break;
+            }
+            for (head, arc) in &self.out[node] {
+                if *head == skip {
+                    continue;
+                }
+                let next = cost + self.arcs[*arc].cost;
+                if next < self.dist[*head] {
+                    if self.dist[*head].is_infinite() {
+                        self.touched.push(*head);
+                    }
+                    self.dist[*head] = next;
+                    heap.push(State { cost: next, node: *head });
+                }
+            }
+        }
+    }
+
+    //shortcuts needed to remove node while keeping every shortest path
+    fn shortcuts(&mut self, node: usize) -> Vec<ChArc> {
+        let incoming: Vec<(usize, usize)> = self.inc[node].iter().map(|(tail, arc)| (*tail, *arc)).collect();
+        let outgoing: Vec<(usize, usize)> = self.out[node].iter().map(|(head, arc)| (*head, *arc)).collect();
+        let mut shortcuts = Vec::new();
+        for (tail, arc_in) in incoming {
+            let first = self.arcs[arc_in];
+            let limit = outgoing.iter()
+                .filter(|(head, _)| *head != tail)
+                .map(|(_, arc_out)| first.cost + self.arcs[*arc_out].cost)
+                .fold(f64::NEG_INFINITY, f64::max);
+            if limit == f64::NEG_INFINITY {
+                continue;
+            }
+            self.witness_search(tail, node, limit);
+            for (head, arc_out) in &outgoing {
+                let second = self.arcs[*arc_out];
+                let cost = first.cost + second.cost;
+                if *head != tail && self.dist[*head] > cost {
+                    shortcuts.push(ChArc { tail, head: *head, cost, length: first.length + second.length, kind: ChArcKind::Shortcut(arc_in, *arc_out) });
+                }
+            }
+        }
+        shortcuts
+    }
+
+    //edge difference plus a penalty for contracting next to already contracted nodes
+    fn priority(&mut self, node: usize) -> i64 {
+        let degree = (self.inc[node].len() + self.out[node].len()) as i64;
+        self.shortcuts(node).len() as i64 - degree + self.deleted_neighbours[node]
+    }
+
+    //removes node, returning its remaining (up, down) arcs
+    fn contract(&mut self, node: usize) -> (Vec<usize>, Vec<usize>) {
+        for shortcut in self.shortcuts(node) {
+            self.add_arc(shortcut);
+        }
+        let up: Vec<usize> = self.out[node].values().copied().collect();
+        let down: Vec<usize> = self.inc[node].values().copied().collect();
+        for arc in &up {
+            let head = self.arcs[*arc].head;
+            self.inc[head].remove(&node);
+            self.deleted_neighbours[head] += 1;
+        }
+        for arc in &down {
+            let tail = self.arcs[*arc].tail;
+            self.out[tail].remove(&node);
+            self.deleted_neighbours[tail] += 1;
+        }
+        self.out[node].clear();
+        self.inc[node].clear();
+        (up, down)
+    }
+}
+
+impl ContractionHierarchy {
+    pub fn new(router: &Router) -> Self {
+        let n = router.node_count();
+        let mut contractor = Contractor::new(router);
+        let mut rank = vec![usize::MAX; n];
+        let mut up = vec![Vec::new(); n];
+        let mut down = vec![Vec::new(); n];
+
+        let mut queue = BinaryHeap::new();
+        for node in 0..n {
+            queue.push(Reverse((contractor.priority(node), node)));
+        }
+        let mut next_rank = 0;
+        while let Some(Reverse((_, node))) = queue.pop() {
+            if rank[node] != usize::MAX {
+                continue;
+            }
+            // lazy update: priorities go stale as neighbours are contracted
+            let priority = contractor.priority(node);
+            if let Some(Reverse((next, _))) = queue.peek() {
+                if priority > *next {
+                    queue.push(Reverse((priority, node)));
+                    continue;
+                }
+            }
+            let (node_up, node_down) = contractor.contract(node);
+            up[node] = node_up;
+            down[node] = node_down;
+            rank[node] = next_rank;
+            next_rank += 1;
+        }
+
+        Self {
+            profile: router.profile,
+            nodes: router.nodes.clone(),
+            rank,
+            arcs: contractor.arcs,
+            up,
+            down,
+        }
+    }
+
+    pub fn save(&self, file_path: &str) -> Result<(), Box<dyn Error>> {
+        let file = File::create(file_path)?;
+        serde_json::to_writer(BufWriter::new(file), self)?;
+        Ok(())
+    }
+
+    pub fn load(file_path: &str) -> Result<Self, Box<dyn Error>> {
+        let file = File::open(file_path)?;
+        Ok(serde_json::from_reader(BufReader::new(file))?)
+    }
+
+    pub fn node_index(&self, osm_id: u64) -> Option<usize> {
+        self.nodes.get(osm_id)
+    }
+
+    //fastest route between two osm node ids
+    pub fn route(&self, source: u64, target: u64) -> Option<Route> {
+        self.query(self.node_index(source)?, self.node_index(target)?)
+    }
+
+    //bidirectional upward search, meeting at the highest ranked node of the shortest path
+    pub fn query(&self, source: usize, target: usize) -> Option<Route> {
+        // <node, (cost, arc used to reach it)>, search spaces are tiny so maps beat full arrays
+        let mut forward: HashMap<usize, (f64, Option<usize>)> = HashMap::new();
+        let mut backward: HashMap<usize, (f64, Option<usize>)> = HashMap::new();
+        let mut forward_heap = BinaryHeap::new();
+        let mut backward_heap = BinaryHeap::new();
+        forward.insert(source, (0.0, None));
+        backward.insert(target, (0.0, None));
+        forward_heap.push(State { cost: 0.0, node: source });
+        backward_heap.push(State { cost: 0.0, node: target });
+        let mut best = f64::INFINITY;
+        let mut meeting = None;
+        if source == target {
+            best = 0.0;
+            meeting = Some(source);
+        }
+
+        loop {
+            let forward_min = forward_heap.peek().map_or(f64::INFINITY, |state: &State| state.cost);
+            let backward_min = backward_heap.peek().map_or(f64::INFINITY, |state: &State| state.cost);
+            if forward_min.min(backward_min) >= best {
+                break;
+            }
+            let is_forward = forward_min <= backward_min;
+            let (heap, this, other, arcs) = if is_forward {
+                (&mut forward_heap, &mut forward, &backward, &self.up)
+            } else {
+                (&mut backward_heap, &mut backward, &forward, &self.down)
+            };
+            let State { cost, node } = heap.pop().unwrap();
+            if cost > this[&node].0 {
+                continue;
+            }
+            if let Some((other_cost, _)) = other.get(&node) {
+                if cost + other_cost < best {
+                    best = cost + other_cost;
+                    meeting = Some(node);
+                }
+            }
+            for arc_index in &arcs[node] {
+                let arc = &self.arcs[*arc_index];
+                let next_node = if is_forward { arc.head } else { arc.tail };
+                let next = cost + arc.cost;
+                if this.get(&next_node).is_none_or(|(known, _)| next < *known) {
+                    this.insert(next_node, (next, Some(*arc_index)));
+                    heap.push(State { cost: next, node: next_node });
+                }
+            }
+        }
+
+        let meeting = meeting?;
+        let mut arcs = Vec::new();
+        let mut current = meeting;
+        while let Some((_, Some(arc))) = forward.get(&current) {
+            arcs.push(*arc);
+            current = self.arcs[*arc].tail;
+        }
+        arcs.reverse();
+        let mut current = meeting;
+        while let Some((_, Some(arc))) = backward.get(&current) {
+            arcs.push(*arc);
+            current = self.arcs[*arc].head;
+        }
+
+        let mut route = Route { distance: 0.0, duration: best, edges: Vec::new() };
+        for arc in arcs {
+            self.unpack(arc, &mut route);
+        }
+        Some(route)
+    }
+
+    //expands a shortcut into the original edges it stands for
+    fn unpack(&self, arc: usize, route: &mut Route) {
+        let mut stack = vec![arc];
+        while let Some(arc) = stack.pop() {
+            match self.arcs[arc].kind {
+                ChArcKind::Original(edge, forward) => {
+                    route.edges.push((edge, forward));
+                    route.distance += self.arcs[arc].length;
+                }
+                ChArcKind::Shortcut(first, second) => {
+                    stack.push(second);
+                    stack.push(first);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ContractionHierarchy;
+    use crate::graph::fixtures::grid;
+    use crate::graph::Graph;
+    use crate::routing::{Profile, Router};
+    use approx::assert_relative_eq;
+
+    fn assert_matches_dijkstra(graph: &Graph, profile: Profile) {
+        let router = Router::new(graph, profile);
+        let ch = ContractionHierarchy::new(&router);
+        for source in 0..router.node_count() {
+            for target in 0..router.node_count() {
+                let expected = router.shortest_path(source, target);
+                let found = ch.query(source, target);
+                assert_eq!(expected.is_some(), found.is_some());
+                if let (Some(expected), Some(found)) = (expected, found) {
+                    assert_relative_eq!(expected.duration, found.duration, epsilon = 1e-6);
+                    assert_relative_eq!(found.duration, found.edges.iter().map(|(edge, forward)| {
+                        let edge = &graph.edges[*edge];
+                        edge.length / profile.speed(edge, *forward).unwrap()
+                    }).sum::<f64>(), epsilon = 1e-6);
+                    // unpacked edges chain from source to target
+                    let mut at = router.nodes.id(source).to_string();
+                    for (edge, forward) in &found.edges {
+                        let edge = &graph.edges[*edge];
+                        let (from, to) = if *forward { (&edge.source, &edge.target) } else { (&edge.target, &edge.source) };
+                        assert_eq!(from, &at);
+                        at = to.clone();
+                    }
+                    assert_eq!(at, router.nodes.id(target).to_string());
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_ch_matches_dijkstra() {
+        assert_matches_dijkstra(&grid(), Profile::Car);
+        assert_matches_dijkstra(&grid(), Profile::Foot);
+        assert_matches_dijkstra(&Graph::from_csv("testedges.csv", "testnodes.csv"), Profile::Foot);
+    }
+
+    #[test]
+    fn test_ch_save_and_load() {
+        let graph = grid();
+        let ch = ContractionHierarchy::new(&Router::new(&graph, Profile::Car));
+        let file_path = std::env::temp_dir().join("algo_test_ch.json");
+        ch.save(file_path.to_str().unwrap()).unwrap();
+        let loaded = ContractionHierarchy::load(file_path.to_str().unwrap()).unwrap();
+        assert_eq!(loaded.rank, ch.rank);
+        assert_eq!(loaded.route(1, 36).unwrap().edges, ch.route(1, 36).unwrap().edges);
+    }
+}
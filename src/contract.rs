@@ -0,0 +1,40 @@
+use std::time::Instant;
+mod graph;
+mod calendar;
+mod transfers;
+mod geodesic;
+mod interner;
+mod routing;
+mod ch;
+use graph::Graph;
+use routing::{Profile, Router};
+use ch::ContractionHierarchy;
+
+fn main() {
+    let args = arguments::parse(std::env::args()).unwrap();
+    let edges = args.get::<String>("edges").unwrap_or_else(|| "testedges.csv".to_string());
+    let nodes = args.get::<String>("nodes").unwrap_or_else(|| "testnodes.csv".to_string());
+    let source = args.get::<u64>("from").unwrap_or(2729443585);
+    let target = args.get::<u64>("to").unwrap_or(2729463686);
+
+    let start_time = Instant::now();
+    let graph = Graph::from_csv(&edges, &nodes);
+    eprintln!("from_csv took {:?}", start_time.elapsed().as_secs_f64());
+
+    for (profile, name) in [(Profile::Foot, "foot"), (Profile::Bike, "bike"), (Profile::Car, "car")] {
+        let start_time = Instant::now();
+        let ch = ContractionHierarchy::new(&Router::new(&graph, profile));
+        eprintln!("{} contraction took {:?}, {} arcs", name, start_time.elapsed().as_secs_f64(), ch.arcs.len());
+        ch.save(&format!("ch_{}.json", name)).unwrap();
+
+        let start_time = Instant::now();
+        match ch.route(source, target) {
+            Some(route) => {
+                println!("{}: distance: {:.1} m, duration: {:.1} s, took {:?}ns", name, route.distance, route.duration, start_time.elapsed().as_nanos());
+                println!("{:?}", route.edge_ids(&graph));
+                println!("{} points", route.geometry(&graph).len());
+            }
+            None => println!("{}: No path found", name),
+        }
+    }
+}
@@ -0,0 +1,433 @@
+use std::{cmp::Reverse, collections::HashMap};
+use serde::Serialize;
+use crate::graph::GTFSGraph;
+use crate::raptor::{Journey, Leg};
+
+// arrival time of stops not reached yet
+const UNREACHED: u32 = u32::MAX;
+
+//one vehicle hop between consecutive stops of a trip
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Connection {
+    //dense stop indices
+    pub departure_stop: usize,
+    pub arrival_stop: usize,
+    //seconds since midnight
+    pub departure: u32,
+    pub arrival: u32,
+    //index into Csa::trip_ids
+    pub trip: usize,
+}
+
+//leaving the origin at departure gets to the destination by arrival
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProfileEntry {
+    pub departure: u32,
+    pub arrival: u32,
+}
+
+//how a stop was reached
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Reached {
+    //first and last connection ridden on one trip
+    Ride(usize, usize),
+    Walk { from: usize, seconds: u32 },
+}
+
+#[derive(Debug, Clone)]
+pub struct Csa {
+    pub stops: Vec<String>,
+    pub stop_index: HashMap<String, usize>,
+    pub trip_ids: Vec<String>,
+    pub trip_routes: Vec<String>,
+    //sorted by departure, then arrival
+    pub connections: Vec<Connection>,
+    //(to stop, seconds) footpaths from each stop
+    pub transfers: Vec<Vec<(usize, u32)>>,
+    //(from stop, seconds) footpaths into each stop
+    pub incoming: Vec<Vec<(usize, u32)>>,
+    //minimum seconds between alighting one vehicle and boarding another at each stop
+    pub change: Vec<u32>,
+    //seconds needed for the changes transfers.txt rules by route or trip, None when ruled out, keyed (from stop,
+    //to stop, from trip, to trip)
+    ruled: HashMap<(usize, usize, usize, usize), Option<u32>>,
+    //stops where a ruled change alights, and where one boards
+    ruled_alighting: Vec<bool>,
+    ruled_boarding: Vec<bool>,
+}
+
+impl Csa {
+    //every trip of gtfs runs in one day, as for Raptor::new
+    pub fn new(gtfs: &GTFSGraph) -> Self {
+        let mut csa = Self {
+            stops: Vec::new(),
+            stop_index: HashMap::new(),
+            trip_ids: Vec::new(),
+            trip_routes: Vec::new(),
+            connections: Vec::new(),
+            transfers: Vec::new(),
+            incoming: Vec::new(),
+            change: Vec::new(),
+            ruled: HashMap::new(),
+            ruled_alighting: Vec::new(),
+            ruled_boarding: Vec::new(),
+        };
+        let mut trip_ids: Vec<&String> = gtfs.trips.keys().collect();
+        trip_ids.sort();
+        for trip_id in trip_ids {
+            let trip = &gtfs.trips[trip_id];
+            let index = csa.trip_ids.len();
+            csa.trip_ids.push(trip_id.clone());
+            csa.trip_routes.push(trip.route_id.clone());
+            for pair in trip.stop_times.windows(2) {
+                let (from, _, departure) = &pair[0];
+                let (to, arrival, _) = &pair[1];
+                let (departure_stop, arrival_stop) = (csa.add_stop(from), csa.add_stop(to));
+                csa.connections.push(Connection { departure_stop, arrival_stop, departure: *departure, arrival: *arrival, trip: index });
+            }
+        }
+        csa.connections.sort_by_key(|connection| (connection.departure, connection.arrival));
+        let mut transfers: Vec<(&(String, String), &u32)> = gtfs.transfers.iter().collect();
+        transfers.sort();
+        for ((from, to), seconds) in transfers {
+            csa.add_transfer(from, to, *seconds);
+        }
+
+        let trip_index: HashMap<&str, usize> = csa.trip_ids.iter().enumerate().map(|(index, id)| (id.as_str(), index)).collect();
+        csa.ruled_alighting = vec![false; csa.stops.len()];
+        csa.ruled_boarding = vec![false; csa.stops.len()];
+        for ((from_stop, to_stop, from_trip, to_trip), seconds) in gtfs.ruled_transfers() {
+            let (Some(from_stop), Some(to_stop), Some(from_trip), Some(to_trip)) =
+                (csa.stop_index.get(&from_stop), csa.stop_index.get(&to_stop), trip_index.get(from_trip.as_str()), trip_index.get(to_trip.as_str())) else {
+                continue;
+            };
+            csa.ruled.insert((*from_stop, *to_stop, *from_trip, *to_trip), seconds);
+            csa.ruled_alighting[*from_stop] = true;
+            csa.ruled_boarding[*to_stop] = true;
+        }
+        csa
+    }
+
+    fn add_stop(&mut self, id: &str) -> usize {
+        if let Some(index) = self.stop_index.get(id) {
+            return *index;
+        }
+        let index = self.stops.len();
+        self.stops.push(id.to_string());
+        self.stop_index.insert(id.to_string(), index);
+        self.transfers.push(Vec::new());
+        self.incoming.push(Vec::new());
+        self.change.push(0);
+        index
+    }
+
+    //one way footpath between two stops served by the timetable, from a stop to itself it is the change time there
+    pub fn add_transfer(&mut self, from: &str, to: &str, seconds: u32) {
+        if let (Some(from), Some(to)) = (self.stop_index.get(from).copied(), self.stop_index.get(to).copied()) {
+            if from == to {
+                self.change[from] = seconds;
+            } else {
+                self.transfers[from].push((to, seconds));
+                self.incoming[to].push((from, seconds));
+            }
+        }
+    }
+
+    //earliest arrival at destination leaving origin no earlier than departure
+    pub fn earliest_arrival(&self, origin: &str, destination: &str, departure: u32) -> Option<Journey> {
+        let (origin, destination) = (*self.stop_index.get(origin)?, *self.stop_index.get(destination)?);
+        let mut arrival = vec![UNREACHED; self.stops.len()];
+        let mut reached: Vec<Option<Reached>> = vec![None; self.stops.len()];
+        // first connection ridden on each boarded trip
+        let mut boarded: Vec<Option<usize>> = vec![None; self.trip_ids.len()];
+
+        arrival[origin] = departure;
+        for (to, seconds) in &self.transfers[origin] {
+            if departure + seconds < arrival[*to] {
+                arrival[*to] = departure + seconds;
+                reached[*to] = Some(Reached::Walk { from: origin, seconds: *seconds });
+            }
+        }
+
+        let start = self.connections.partition_point(|connection| connection.departure < departure);
+        for (index, connection) in self.connections.iter().enumerate().skip(start) {
+            // nothing leaving later can arrive earlier
+            if connection.departure >= arrival[destination] {
+                break;
+            }
+            if boarded[connection.trip].is_none() && self.can_board(connection, &arrival, &reached) {
+                boarded[connection.trip] = Some(index);
+            }
+            let Some(first) = boarded[connection.trip] else {
+                continue;
+            };
+            if connection.arrival < arrival[connection.arrival_stop] {
+                arrival[connection.arrival_stop] = connection.arrival;
+                reached[connection.arrival_stop] = Some(Reached::Ride(first, index));
+                for (to, seconds) in &self.transfers[connection.arrival_stop] {
+                    if connection.arrival + seconds < arrival[*to] {
+                        arrival[*to] = connection.arrival + seconds;
+                        reached[*to] = Some(Reached::Walk { from: connection.arrival_stop, seconds: *seconds });
+                    }
+                }
+            }
+        }
+        if arrival[destination] == UNREACHED {
+            return None;
+        }
+
+        let mut legs = Vec::new();
+        let mut stop = destination;
+        while stop != origin {
+            match reached[stop]? {
+                Reached::Ride(first, last) => {
+                    let (first, last) = (self.connections[first], self.connections[last]);
+                    legs.push(Leg::Transit {
+                        route_id: self.trip_routes[first.trip].clone(),
+                        trip_id: self.trip_ids[first.trip].clone(),
+                        board_stop: self.stops[first.departure_stop].clone(),
+                        board_time: first.departure,
+                        alight_stop: self.stops[last.arrival_stop].clone(),
+                        alight_time: last.arrival,
+                    });
+                    stop = first.departure_stop;
+                }
+                Reached::Walk { from, seconds } => {
+                    legs.push(Leg::Transfer { from_stop: self.stops[from].clone(), to_stop: self.stops[stop].clone(), departure: arrival[stop] - seconds, arrival: arrival[stop] });
+                    stop = from;
+                }
+            }
+        }
+        legs.reverse();
+        let transfers = legs.iter().filter(|leg| matches!(leg, Leg::Transit { .. })).count().saturating_sub(1);
+        Some(Journey { departure, arrival: arrival[destination], transfers, legs })
+    }
+
+    //every departure from origin between from and to that is not beaten by a later departure arriving no later, earliest first
+    pub fn profile(&self, origin: &str, destination: &str, from: u32, to: u32) -> Vec<ProfileEntry> {
+        let (Some(origin), Some(destination)) = (self.stop_index.get(origin).copied(), self.stop_index.get(destination).copied()) else {
+            return Vec::new();
+        };
+        // already there, nothing worth riding
+        if origin == destination {
+            return Vec::new();
+        }
+        // per stop, entries pushed in decreasing departure and strictly decreasing arrival, for boarding there and for
+        // walking on to board elsewhere
+        let mut boarding: Vec<Vec<ProfileEntry>> = vec![Vec::new(); self.stops.len()];
+        let mut walking: Vec<Vec<ProfileEntry>> = vec![Vec::new(); self.stops.len()];
+        // every departure with its trip where a ruled change boards, the rules may leave out the best ones
+        let mut ruled_departures: Vec<Vec<(ProfileEntry, usize)>> = vec![Vec::new(); self.stops.len()];
+        let mut trip_arrival = vec![UNREACHED; self.trip_ids.len()];
+        let walk_to_destination: HashMap<usize, u32> = self.incoming[destination].iter().copied().collect();
+
+        let start = self.connections.partition_point(|connection| connection.departure < from);
+        for connection in self.connections[start..].iter().rev() {
+            let alight = if connection.arrival_stop == destination {
+                connection.arrival
+            } else {
+                walk_to_destination.get(&connection.arrival_stop).map_or(UNREACHED, |seconds| connection.arrival + seconds)
+            };
+            let stay = trip_arrival[connection.trip];
+            // as in earliest_arrival, the change time is for boarding at the stop, walking on starts straight away
+            let change = if self.ruled_alighting[connection.arrival_stop] {
+                self.ruled_change(connection, &boarding, &ruled_departures)
+            } else {
+                evaluate(&boarding[connection.arrival_stop], connection.arrival.saturating_add(self.change[connection.arrival_stop]))
+                    .min(evaluate(&walking[connection.arrival_stop], connection.arrival))
+            };
+            let best = alight.min(stay).min(change);
+            if best == UNREACHED {
+                continue;
+            }
+            trip_arrival[connection.trip] = best;
+            let entry = ProfileEntry { departure: connection.departure, arrival: best };
+            insert(&mut boarding[connection.departure_stop], entry);
+            if self.ruled_boarding[connection.departure_stop] {
+                ruled_departures[connection.departure_stop].push((entry, connection.trip));
+            }
+            for (from_stop, seconds) in &self.incoming[connection.departure_stop] {
+                if let Some(departure) = connection.departure.checked_sub(*seconds) {
+                    insert(&mut walking[*from_stop], ProfileEntry { departure, arrival: best });
+                }
+            }
+        }
+
+        // nobody starts at the origin off a vehicle, so both ways of leaving it count as they are
+        let mut entries: Vec<ProfileEntry> = boarding[origin].iter().chain(&walking[origin]).copied().filter(|entry| entry.departure >= from && entry.departure <= to).collect();
+        entries.sort_by_key(|entry| (Reverse(entry.departure), entry.arrival));
+        let mut profile = Vec::new();
+        for entry in entries {
+            insert(&mut profile, entry);
+        }
+        profile.reverse();
+        profile
+    }
+
+    // riders who came in on a vehicle need the stop's change time before boarding another, or what transfers.txt
+    // rules for the two trips
+    fn can_board(&self, connection: &Connection, arrival: &[u32], reached: &[Option<Reached>]) -> bool {
+        let stop = connection.departure_stop;
+        let ruled = if self.ruled_boarding[stop] {
+            self.alighted(stop, arrival, reached).and_then(|(from, trip, at)| Some((at, *self.ruled.get(&(from, stop, trip, connection.trip))?)))
+        } else {
+            None
+        };
+        match ruled {
+            Some((at, Some(seconds))) => arrival[stop].max(at.saturating_add(seconds)) <= connection.departure,
+            Some((_, None)) => false,
+            None => {
+                let ready = match reached[stop] {
+                    Some(Reached::Ride(..)) => arrival[stop].saturating_add(self.change[stop]),
+                    _ => arrival[stop],
+                };
+                ready <= connection.departure
+            }
+        }
+    }
+
+    // stop, trip and time of the last alighting before being at stop, walking on after it or not
+    fn alighted(&self, stop: usize, arrival: &[u32], reached: &[Option<Reached>]) -> Option<(usize, usize, u32)> {
+        let stop = match reached[stop]? {
+            Reached::Walk { from, .. } => from,
+            Reached::Ride(..) => stop,
+        };
+        match reached[stop]? {
+            Reached::Ride(_, last) => Some((stop, self.connections[last].trip, arrival[stop])),
+            Reached::Walk { .. } => None,
+        }
+    }
+
+    // earliest arrival changing off the connection's trip at a stop where transfers.txt rules changes for some trips,
+    // at the stop itself or at the end of a footpath from it
+    fn ruled_change(&self, connection: &Connection, boarding: &[Vec<ProfileEntry>], ruled_departures: &[Vec<(ProfileEntry, usize)>]) -> u32 {
+        let stop = connection.arrival_stop;
+        let targets = std::iter::once((stop, self.change[stop])).chain(self.transfers[stop].iter().copied());
+        targets.map(|(to, seconds)| {
+            let ready = connection.arrival.saturating_add(seconds);
+            if !self.ruled_boarding[to] {
+                return evaluate(&boarding[to], ready);
+            }
+            // rules replace the change time, not the walk
+            let there = if to == stop { connection.arrival } else { ready };
+            ruled_departures[to].iter()
+                .take_while(|(entry, _)| entry.departure >= connection.arrival)
+                .filter(|(entry, trip)| match self.ruled.get(&(stop, to, connection.trip, *trip)) {
+                    Some(Some(seconds)) => entry.departure >= there.max(connection.arrival.saturating_add(*seconds)),
+                    Some(None) => false,
+                    None => entry.departure >= ready,
+                })
+                .map(|(entry, _)| entry.arrival)
+                .min()
+                .unwrap_or(UNREACHED)
+        }).min().unwrap_or(UNREACHED)
+    }
+}
+
+//earliest arrival in a profile when ready to leave at time
+fn evaluate(profile: &[ProfileEntry], time: u32) -> u32 {
+    let later = profile.partition_point(|entry| entry.departure >= time);
+    if later == 0 { UNREACHED } else { profile[later - 1].arrival }
+}
+
+// entries come in non-increasing departure order, so only the last one can dominate the new one
+fn insert(profile: &mut Vec<ProfileEntry>, entry: ProfileEntry) {
+    match profile.last_mut() {
+        Some(last) if last.arrival <= entry.arrival => {}
+        Some(last) if last.departure == entry.departure => last.arrival = entry.arrival,
+        _ => profile.push(entry),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Csa, ProfileEntry};
+    use crate::graph::GTFSGraph;
+    use crate::raptor::fixtures::{self, hms, trip};
+    use crate::raptor::{Leg, Raptor};
+    use crate::transfers::{StationRules, TransferRule, TransferType};
+
+    // the router fixture with a later blue trip, so red-2 has a change too
+    fn feed() -> GTFSGraph {
+        let mut gtfs = fixtures::feed();
+        gtfs.add_trip("blue-2".to_string(), "blue".to_string(), "weekday".to_string(), trip(&[("B", hms(8, 45)), ("E", hms(9, 5))]));
+        gtfs
+    }
+
+    #[test]
+    fn test_earliest_arrival_matches_raptor() {
+        let gtfs = feed();
+        let (csa, raptor) = (Csa::new(&gtfs), Raptor::new(&gtfs));
+        assert!(csa.connections.windows(2).all(|pair| pair[0].departure <= pair[1].departure));
+        for departure in (hms(7, 50)..hms(9, 0)).step_by(300) {
+            for (origin, destination) in [("A", "E"), ("A", "D"), ("B", "E"), ("C", "A")] {
+                let found = csa.earliest_arrival(origin, destination, departure).map(|journey| journey.arrival);
+                let expected = raptor.earliest_arrival(origin, destination, departure, 5).map(|journey| journey.arrival);
+                assert_eq!(found, expected);
+            }
+        }
+        let journey = csa.earliest_arrival("A", "E", hms(7, 55)).unwrap();
+        assert_eq!(journey.transfers, 1);
+        assert!(matches!(&journey.legs[0], Leg::Transit { trip_id, alight_stop, .. } if trip_id == "red-1" && alight_stop == "B"));
+    }
+
+    #[test]
+    fn test_profile() {
+        let mut csa = Csa::new(&feed());
+        let entries = csa.profile("A", "E", hms(7, 0), hms(10, 0));
+        assert_eq!(entries, vec![
+            ProfileEntry { departure: hms(8, 0), arrival: hms(8, 35) },
+            ProfileEntry { departure: hms(8, 5), arrival: hms(8, 50) },
+            ProfileEntry { departure: hms(8, 30), arrival: hms(9, 5) },
+        ]);
+        assert_eq!(csa.profile("A", "E", hms(8, 1), hms(8, 20)), vec![ProfileEntry { departure: hms(8, 5), arrival: hms(8, 50) }]);
+
+        csa.add_transfer("C", "E", 600);
+        let entries = csa.profile("A", "E", hms(7, 0), hms(10, 0));
+        assert_eq!(entries[0], ProfileEntry { departure: hms(8, 0), arrival: hms(8, 30) });
+        assert_eq!(entries[2], ProfileEntry { departure: hms(8, 30), arrival: hms(9, 0) });
+
+        // only the second blue trip leaves B in the window
+        let entries = csa.profile("B", "E", hms(8, 41), hms(9, 0));
+        assert_eq!(entries, vec![ProfileEntry { departure: hms(8, 45), arrival: hms(9, 5) }]);
+    }
+
+    #[test]
+    fn test_minimum_change_time() {
+        let mut gtfs = feed();
+        let rule = TransferRule { from_stop: Some("B".to_string()), to_stop: Some("B".to_string()), from_route: None, to_route: None, from_trip: None, to_trip: None, transfer_type: TransferType::MinimumTime, min_transfer_time: Some(600) };
+        gtfs.add_station_rules(StationRules { transfers: vec![rule.clone()], ..Default::default() });
+        assert_eq!(gtfs.transfer_time("B", "B", "red-1", "blue-1"), Some(600));
+        let (csa, raptor) = (Csa::new(&gtfs), Raptor::new(&gtfs));
+        // ten minutes at B misses the 8:15 blue trip, so the express wins
+        for departure in (hms(7, 50)..hms(9, 0)).step_by(300) {
+            let found = csa.earliest_arrival("A", "E", departure).map(|journey| journey.arrival);
+            assert_eq!(found, raptor.earliest_arrival("A", "E", departure, 5).map(|journey| journey.arrival));
+        }
+        assert_eq!(csa.earliest_arrival("A", "E", hms(7, 55)).unwrap().legs.len(), 1);
+        assert_eq!(csa.profile("A", "E", hms(7, 0), hms(10, 0)), vec![ProfileEntry { departure: hms(8, 5), arrival: hms(8, 50) }]);
+
+        // blue-1 waiting for red-1 lifts it for that one change
+        let timed = TransferRule { from_trip: Some("red-1".to_string()), to_trip: Some("blue-1".to_string()), transfer_type: TransferType::Timed, min_transfer_time: None, ..rule };
+        gtfs.add_station_rules(StationRules { transfers: vec![timed], ..Default::default() });
+        let csa = Csa::new(&gtfs);
+        assert_eq!(csa.earliest_arrival("A", "E", hms(7, 55)).unwrap().arrival, hms(8, 35));
+        assert_eq!(csa.profile("A", "E", hms(7, 0), hms(8, 0)), vec![ProfileEntry { departure: hms(8, 0), arrival: hms(8, 35) }]);
+    }
+
+    #[test]
+    fn test_change_time_before_walking_on() {
+        // ten minutes to change at B, but walking on to the green line at F starts straight away
+        let mut gtfs = feed();
+        gtfs.add_trip("green-1".to_string(), "green".to_string(), "weekday".to_string(), trip(&[("F", hms(8, 12)), ("E", hms(8, 30))]));
+        gtfs.transfers.insert(("B".to_string(), "B".to_string()), 600);
+        gtfs.transfers.insert(("B".to_string(), "F".to_string()), 60);
+        let (csa, raptor) = (Csa::new(&gtfs), Raptor::new(&gtfs));
+        let entries = csa.profile("A", "E", hms(7, 0), hms(10, 0));
+        assert_eq!(entries[0], ProfileEntry { departure: hms(8, 0), arrival: hms(8, 30) });
+        for entry in &entries {
+            assert_eq!(csa.earliest_arrival("A", "E", entry.departure).map(|journey| journey.arrival), Some(entry.arrival));
+            assert_eq!(raptor.earliest_arrival("A", "E", entry.departure, 5).map(|journey| journey.arrival), Some(entry.arrival));
+        }
+    }
+}
@@ -1,5 +1,11 @@
 extern crate csv;
 extern crate petgraph;
+mod graph;
+mod calendar;
+mod transfers;
+mod geodesic;
+mod interner;
+use interner::NodeInterner;
 use petgraph::algo::dijkstra;
 use petgraph::graph::{DiGraph, NodeIndex};
 
@@ -38,8 +44,9 @@ fn main() -> Result<(), Box<dyn Error>> {
     let file = File::open("edges.csv")?;
     let mut rdr = csv::ReaderBuilder::new().has_headers(true).from_reader(file);
 
-    // Create a directed graph with usize as the index type
-    let mut graph = DiGraph::<Edge, f64, usize>::with_capacity(0, 0);
+    // Create a directed graph with usize as the index type, nodes carrying their osm node id and edges their length
+    let mut graph = DiGraph::<u64, f64, usize>::with_capacity(0, 0);
+    let mut interner = NodeInterner::new();
 
     // Iterate over CSV records and add edges to the graph
     for result in rdr.records() {
@@ -59,18 +66,23 @@ fn main() -> Result<(), Box<dyn Error>> {
             wkt: record[11].to_string(),
         };
 
-        let source_node = NodeIndex::new((edge.source as u64).try_into().unwrap()       );
-        let target_node = NodeIndex::new((edge.target as u64).try_into().unwrap());
+        // petgraph indices have to be dense, osm ids are not
+        let source_node = NodeIndex::new(interner.intern(edge.source));
+        let target_node = NodeIndex::new(interner.intern(edge.target));
+        while graph.node_count() < interner.len() {
+            let id = interner.id(graph.node_count());
+            graph.add_node(id);
+        }
 
         // Add nodes and edges to the graph
         graph.add_edge(source_node, target_node, edge.length);
     }
 
     // Find the shortest foot path using Dijkstra's algorithm
-    let start_node = NodeIndex::new(1833121478); // Node 1
-    let end_node = NodeIndex::new(1597291791); // Node 100
+    let start_node = NodeIndex::new(interner.get(1833121478).ok_or("start node not in edges.csv")?);
+    let end_node = NodeIndex::new(interner.get(1597291791).ok_or("end node not in edges.csv")?);
     let route = dijkstra(&graph, start_node, Some(end_node), |e| *e.weight());
-    println!("{:#?}", route);
+    println!("{:#?}", route.get(&end_node));
 
     Ok(())
 }
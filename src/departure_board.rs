@@ -0,0 +1,44 @@
+use std::{error::Error, time::Instant};
+use chrono::{Duration, NaiveDateTime, Utc};
+mod graph;
+mod calendar;
+mod transfers;
+mod geodesic;
+mod interner;
+use calendar::utc_offset;
+use graph::GTFSGraph;
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let args = arguments::parse(std::env::args()).unwrap();
+    let feed = args.get::<String>("feed").unwrap_or_else(|| "gtfs_rail.zip".to_string());
+    let stop = args.get::<String>("stop").unwrap_or_else(|| "80101".to_string());
+    let count = args.get::<usize>("count").unwrap_or(10);
+    //YYYY-MM-DDTHH:MM agency local time, now if not given
+    let at = args.get::<String>("at").and_then(|at| NaiveDateTime::parse_from_str(&at, "%Y-%m-%dT%H:%M").ok());
+
+    let start_time = Instant::now();
+    // the agency's date is within a day of utc's, two days either side also covers the service days around it
+    let around = at.map(|at| at.date()).unwrap_or_else(|| Utc::now().date_naive());
+    let gtfs = GTFSGraph::from_file_between(&feed, "f-9q5-metro~losangeles~rail", around - Duration::days(2), around + Duration::days(2));
+    eprintln!("from_file_between took {:?}", start_time.elapsed().as_secs_f64());
+    // a board in the wrong timezone is worse than none
+    let at = match at {
+        Some(at) => at,
+        None => {
+            let now = Utc::now();
+            now.with_timezone(&utc_offset(&gtfs.calendar.timezone, now)?).naive_local()
+        }
+    };
+
+    let start_time = Instant::now();
+    let board = gtfs.next_departures(&stop, at, count);
+    eprintln!("next_departures took {:?}", start_time.elapsed().as_secs_f64());
+    println!("{} from {}", gtfs.stop_names.get(&stop).unwrap_or(&stop), at.format("%Y-%m-%d %H:%M"));
+    if board.is_empty() {
+        println!("No departures");
+    }
+    for row in board {
+        println!("  {} {} to {} from {}, trip {}", row.time.format("%H:%M"), row.route_name, row.headsign, gtfs.stop_names.get(&row.stop_id).unwrap_or(&row.stop_id), row.trip_id);
+    }
+    Ok(())
+}
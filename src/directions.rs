@@ -0,0 +1,261 @@
+use std::{collections::{HashMap, HashSet}, error::Error, fs::File};
+use csv::ReaderBuilder;
+use geographiclib_rs::{Geodesic, InverseGeodesic};
+use serde::{Serialize, Deserialize};
+use crate::graph::{Graph, Node};
+use crate::routing::Route;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ManeuverKind {
+    Depart,
+    Continue,
+    SlightLeft,
+    Left,
+    SharpLeft,
+    SlightRight,
+    Right,
+    SharpRight,
+    UTurn,
+    //enter a roundabout and leave at the given exit
+    Roundabout(usize),
+    Arrive,
+}
+
+impl ManeuverKind {
+    //from the change of heading in degrees, positive turning clockwise
+    pub fn from_turn(turn: f64) -> Self {
+        let angle = turn.abs();
+        if angle < 20.0 {
+            ManeuverKind::Continue
+        } else if angle >= 170.0 {
+            ManeuverKind::UTurn
+        } else if turn > 0.0 {
+            if angle < 45.0 { ManeuverKind::SlightRight } else if angle < 135.0 { ManeuverKind::Right } else { ManeuverKind::SharpRight }
+        } else if angle < 45.0 {
+            ManeuverKind::SlightLeft
+        } else if angle < 135.0 {
+            ManeuverKind::Left
+        } else {
+            ManeuverKind::SharpLeft
+        }
+    }
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct Maneuver {
+    pub kind: ManeuverKind,
+    pub street: Option<String>,
+    pub lon: f64,
+    pub lat: f64,
+    //metres from the start of the route
+    pub distance: f64,
+    //metres until the next maneuver
+    pub length: f64,
+}
+
+impl Maneuver {
+    pub fn instruction(&self) -> String {
+        let action = match self.kind {
+            ManeuverKind::Depart => "Head out".to_string(),
+            ManeuverKind::Continue => "Continue".to_string(),
+            ManeuverKind::SlightLeft => "Bear left".to_string(),
+            ManeuverKind::Left => "Turn left".to_string(),
+            ManeuverKind::SharpLeft => "Turn sharp left".to_string(),
+            ManeuverKind::SlightRight => "Bear right".to_string(),
+            ManeuverKind::Right => "Turn right".to_string(),
+            ManeuverKind::SharpRight => "Turn sharp right".to_string(),
+            ManeuverKind::UTurn => "Make a U-turn".to_string(),
+            ManeuverKind::Roundabout(exit) => format!("At the roundabout take exit {}", exit),
+            ManeuverKind::Arrive => return "Arrive at your destination".to_string(),
+        };
+        match &self.street {
+            Some(street) => format!("{} onto {}", action, street),
+            None => action,
+        }
+    }
+}
+
+//way attributes edges.csv does not carry, keyed by osm way id
+#[derive(Debug, Clone, Default)]
+pub struct StreetInfo {
+    pub names: HashMap<String, String>,
+    pub roundabouts: HashSet<String>,
+}
+
+#[derive(Deserialize)]
+struct StreetRecord {
+    osm_id: String,
+    name: String,
+    junction: String,
+}
+
+impl StreetInfo {
+    //csv with osm_id,name,junction columns
+    pub fn from_csv(file_path: &str) -> Result<Self, Box<dyn Error>> {
+        let file = File::open(file_path)?;
+        let mut rdr = ReaderBuilder::new().from_reader(file);
+        let mut streets = Self::default();
+        for result in rdr.deserialize::<StreetRecord>() {
+            let record = result?;
+            if !record.name.is_empty() {
+                streets.names.insert(record.osm_id.clone(), record.name);
+            }
+            if record.junction == "roundabout" {
+                streets.roundabouts.insert(record.osm_id);
+            }
+        }
+        Ok(streets)
+    }
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct Directions {
+    //(lon, lat) in travel order
+    pub geometry: Vec<(f64, f64)>,
+    //metres from the start at each geometry point
+    pub cumulative: Vec<f64>,
+    pub maneuvers: Vec<Maneuver>,
+}
+
+//heading into the last point and out of the first point of a travel-ordered linestring
+fn bearings(geod: &Geodesic, linestring: &[Node]) -> (f64, f64) {
+    let n = linestring.len();
+    if n < 2 {
+        return (0.0, 0.0);
+    }
+    let (_, azi_out, _, _) = geod.inverse(linestring[0].lat, linestring[0].lon, linestring[1].lat, linestring[1].lon);
+    let (_, _, azi_in, _) = geod.inverse(linestring[n - 2].lat, linestring[n - 2].lon, linestring[n - 1].lat, linestring[n - 1].lon);
+    (azi_in, azi_out)
+}
+
+//turn from heading before to heading after, in (-180, 180]
+fn turn_angle(before: f64, after: f64) -> f64 {
+    let mut turn = (after - before) % 360.0;
+    if turn > 180.0 {
+        turn -= 360.0;
+    } else if turn <= -180.0 {
+        turn += 360.0;
+    }
+    turn
+}
+
+fn node_degree<'a>(graph: &'a Graph, degree: &mut Option<HashMap<&'a str, usize>>, node: &str) -> usize {
+    let degree = degree.get_or_insert_with(|| {
+        let mut degree = HashMap::new();
+        for edge in &graph.edges {
+            *degree.entry(edge.source.as_str()).or_default() += 1;
+            *degree.entry(edge.target.as_str()).or_default() += 1;
+        }
+        degree
+    });
+    degree.get(node).copied().unwrap_or(0)
+}
+
+pub fn directions(graph: &Graph, route: &Route, streets: &StreetInfo) -> Directions {
+    let geod = Geodesic::wgs84();
+    let geometry: Vec<(f64, f64)> = route.geometry(graph).iter().map(|node| (node.lon, node.lat)).collect();
+    let mut cumulative: Vec<f64> = vec![0.0; geometry.len()];
+    for i in 1..geometry.len() {
+        let ((from_lon, from_lat), (to_lon, to_lat)) = (geometry[i - 1], geometry[i]);
+        let step: f64 = geod.inverse(from_lat, from_lon, to_lat, to_lon);
+        cumulative[i] = cumulative[i - 1] + step;
+    }
+    let mut maneuvers: Vec<Maneuver> = Vec::new();
+
+    // edges touching each osm node, only needed to count roundabout exits
+    let mut degree: Option<HashMap<&str, usize>> = None;
+
+    let mut previous: Option<(usize, f64)> = None;
+    let mut roundabout_entry: Option<usize> = None;
+    // index into geometry where the current edge starts
+    let mut offset = 0;
+    for (edge_index, forward) in &route.edges {
+        let edge = &graph.edges[*edge_index];
+        let mut linestring = edge.linestring.clone();
+        if !forward {
+            linestring.reverse();
+        }
+        let (bearing_in, bearing_out) = bearings(&geod, &linestring);
+        let start = cumulative.get(offset).copied().unwrap_or(0.0);
+        let street = streets.names.get(&edge.osm_id).cloned();
+        let on_roundabout = streets.roundabouts.contains(&edge.osm_id);
+        let entry_node = if *forward { &edge.source } else { &edge.target };
+
+        match previous {
+            None => maneuvers.push(Maneuver { kind: ManeuverKind::Depart, street: street.clone(), lon: linestring[0].lon, lat: linestring[0].lat, distance: 0.0, length: 0.0 }),
+            Some((previous_edge, previous_bearing)) => {
+                let previous_osm_id = &graph.edges[previous_edge].osm_id;
+                if on_roundabout {
+                    if roundabout_entry.is_none() {
+                        roundabout_entry = Some(maneuvers.len());
+                        maneuvers.push(Maneuver { kind: ManeuverKind::Roundabout(0), street: None, lon: linestring[0].lon, lat: linestring[0].lat, distance: start, length: 0.0 });
+                    } else if node_degree(graph, &mut degree, entry_node) > 2 {
+                        // passing a junction on the ring
+                        if let Some(ManeuverKind::Roundabout(exit)) = roundabout_entry.map(|i| &mut maneuvers[i].kind) {
+                            *exit += 1;
+                        }
+                    }
+                } else if let Some(entry) = roundabout_entry.take() {
+                    if let ManeuverKind::Roundabout(exit) = &mut maneuvers[entry].kind {
+                        *exit += 1;
+                    }
+                    maneuvers[entry].street = street.clone();
+                } else {
+                    let kind = ManeuverKind::from_turn(turn_angle(previous_bearing, bearing_out));
+                    let renamed = previous_osm_id != &edge.osm_id && streets.names.get(previous_osm_id) != street.as_ref();
+                    if kind != ManeuverKind::Continue || renamed {
+                        maneuvers.push(Maneuver { kind, street: street.clone(), lon: linestring[0].lon, lat: linestring[0].lat, distance: start, length: 0.0 });
+                    }
+                }
+            }
+        }
+
+        // consecutive edges share their joining node
+        offset += linestring.len().saturating_sub(1);
+        previous = Some((*edge_index, bearing_in));
+    }
+
+    if let (Some((lon, lat)), Some(total)) = (geometry.last().copied(), cumulative.last().copied()) {
+        maneuvers.push(Maneuver { kind: ManeuverKind::Arrive, street: None, lon, lat, distance: total, length: 0.0 });
+    }
+    for i in 1..maneuvers.len() {
+        maneuvers[i - 1].length = maneuvers[i].distance - maneuvers[i - 1].distance;
+    }
+    Directions { geometry, cumulative, maneuvers }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{directions, ManeuverKind, StreetInfo};
+    use crate::graph::fixtures::path;
+    use crate::graph::{Edge, Graph, Node};
+    use crate::routing::{Profile, Router};
+    use approx::assert_relative_eq;
+
+    #[test]
+    fn test_turns_and_street_names() {
+        // north up Main St for two blocks, then right onto Ocean Ave, walking that edge against its digitised direction
+        let mut graph = Graph::new();
+        let nodes = [Node::new(1, -118.0, 34.0), Node::new(2, -118.0, 34.001), Node::new(3, -118.0, 34.002), Node::new(4, -117.999, 34.002)];
+        for node in nodes {
+            graph.add_node_obj(node);
+        }
+        for (id, osm_id, a, b) in [("main-0", "main", 0, 1), ("main-1", "main", 1, 2), ("ocean-0", "ocean", 3, 2)] {
+            graph.add_edge_obj(Edge { osm_id: osm_id.to_string(), ..path(id, nodes[a], nodes[b], 100.0) });
+        }
+        let mut streets = StreetInfo::default();
+        streets.names.insert("main".to_string(), "Main St".to_string());
+        streets.names.insert("ocean".to_string(), "Ocean Ave".to_string());
+
+        let route = Router::new(&graph, Profile::Foot).route(1, 4).unwrap();
+        let directions = directions(&graph, &route, &streets);
+        assert_eq!(directions.geometry.len(), 4);
+        assert_eq!(directions.geometry[3], (-117.999, 34.002));
+        let kinds: Vec<ManeuverKind> = directions.maneuvers.iter().map(|maneuver| maneuver.kind).collect();
+        assert_eq!(kinds, vec![ManeuverKind::Depart, ManeuverKind::Right, ManeuverKind::Arrive]);
+        assert_eq!(directions.maneuvers[1].instruction(), "Turn right onto Ocean Ave");
+        assert_relative_eq!(directions.maneuvers[1].distance, directions.cumulative[2]);
+        assert_relative_eq!(directions.maneuvers[0].length, directions.cumulative[2]);
+        assert!(directions.cumulative.windows(2).all(|pair| pair[1] > pair[0]));
+    }
+}
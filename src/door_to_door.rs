@@ -0,0 +1,65 @@
+use std::{fs, time::Instant};
+use chrono::NaiveDate;
+mod graph;
+mod calendar;
+mod transfers;
+mod geodesic;
+mod interner;
+mod routing;
+mod snap;
+mod raptor;
+mod multimodal;
+use graph::{format_time, Graph, GTFSGraph, TRANSFER_WALK_SPEED};
+use multimodal::{Planner, Step};
+
+fn main() {
+    let args = arguments::parse(std::env::args()).unwrap();
+    let edges = args.get::<String>("edges").unwrap_or_else(|| "edges.csv".to_string());
+    let nodes = args.get::<String>("nodes").unwrap_or_else(|| "nodes.csv".to_string());
+    let feed = args.get::<String>("feed").unwrap_or_else(|| "gtfs_rail.zip".to_string());
+    let from = (args.get::<f64>("from_lon").unwrap_or(-118.2437), args.get::<f64>("from_lat").unwrap_or(34.0522));
+    let to = (args.get::<f64>("to_lon").unwrap_or(-118.1445), args.get::<f64>("to_lat").unwrap_or(34.1478));
+    let departure = args.get::<u32>("departure").unwrap_or(8 * 3600);
+    let max_transfers = args.get::<usize>("transfers").unwrap_or(3);
+    //metres, for each walk on its own
+    let max_walk = args.get::<f64>("max_walk").unwrap_or(800.0);
+    //metres per second
+    let walk_speed = args.get::<f64>("walk_speed").unwrap_or(TRANSFER_WALK_SPEED);
+
+    let start_time = Instant::now();
+    let graph = Graph::from_csv(&edges, &nodes);
+    eprintln!("from_csv took {:?}", start_time.elapsed().as_secs_f64());
+    let start_time = Instant::now();
+    //YYYY-MM-DD service day, today in the agency's timezone if not given
+    let gtfs = match args.get::<String>("date").and_then(|date| NaiveDate::parse_from_str(&date, "%Y-%m-%d").ok()) {
+        Some(date) => GTFSGraph::from_file_between(&feed, "f-9q5-metro~losangeles~rail", date, date),
+        None => GTFSGraph::from_file(&feed, "f-9q5-metro~losangeles~rail"),
+    };
+    eprintln!("from_file took {:?}", start_time.elapsed().as_secs_f64());
+    let start_time = Instant::now();
+    let planner = Planner::new(&graph, &gtfs, max_walk, walk_speed);
+    eprintln!("Planner::new took {:?}", start_time.elapsed().as_secs_f64());
+
+    let start_time = Instant::now();
+    let itineraries = planner.plan(from, to, departure, max_transfers);
+    eprintln!("plan took {:?}", start_time.elapsed().as_secs_f64());
+    if itineraries.is_empty() {
+        println!("No itinerary found");
+        return;
+    }
+    let name = |stop: &String| gtfs.stop_names.get(stop).cloned().unwrap_or_else(|| stop.clone());
+    for itinerary in &itineraries {
+        println!("depart {} arrive {}, {} min, {:.0} m walked, {} transfers", format_time(itinerary.departure), format_time(itinerary.arrival), itinerary.duration / 60, itinerary.walk_distance, itinerary.transfers);
+        for step in &itinerary.steps {
+            match step {
+                Step::Walk { from, to, departure, arrival, distance, .. } => {
+                    println!("  {} walk {:.0} m {} -> {} {}", format_time(*departure), distance, name(from), name(to), format_time(*arrival));
+                }
+                Step::Transit { route_id, trip_id, board_stop, board_time, alight_stop, alight_time } => {
+                    println!("  {} {} -> {} {}: {} trip {}", format_time(*board_time), name(board_stop), name(alight_stop), format_time(*alight_time), gtfs.route_names.get(route_id).unwrap_or(route_id), trip_id);
+                }
+            }
+        }
+    }
+    fs::write("itineraries.json", serde_json::to_string(&itineraries).unwrap()).unwrap();
+}
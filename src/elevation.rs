@@ -0,0 +1,262 @@
+use std::{collections::{BinaryHeap, HashMap}, cmp::Ordering, error::Error, fs::File};
+use csv::ReaderBuilder;
+use serde::{Serialize, Deserialize};
+use crate::graph::Graph;
+use crate::routing::{Arc, Route, Router};
+
+// metres of ascent two labels may differ by and still count as equal, keeps the pareto set from filling up with near duplicates
+const ASCENT_EPSILON: f64 = 0.5;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Surface {
+    Paved,
+    Compacted,
+    Unpaved,
+    Unknown,
+}
+
+impl Surface {
+    //from the osm surface=* tag
+    pub fn from_tag(tag: &str) -> Self {
+        match tag {
+            "asphalt" | "paved" | "concrete" | "concrete:plates" | "paving_stones" | "chipseal" => Surface::Paved,
+            "compacted" | "fine_gravel" | "sett" | "cobblestone" | "unhewn_cobblestone" => Surface::Compacted,
+            "unpaved" | "gravel" | "dirt" | "ground" | "earth" | "grass" | "sand" | "mud" | "pebblestone" | "woodchips" => Surface::Unpaved,
+            _ => Surface::Unknown,
+        }
+    }
+
+    //extra metres of effort per metre ridden, on top of the distance itself
+    pub fn penalty(&self) -> f64 {
+        match self {
+            Surface::Paved => 0.0,
+            Surface::Compacted => 0.3,
+            Surface::Unpaved => 1.0,
+            Surface::Unknown => 0.1,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct BikeWeights {
+    //per metre ridden
+    pub distance: f64,
+    //per metre climbed
+    pub climb: f64,
+    //multiplies Surface::penalty
+    pub surface: f64,
+}
+
+impl Default for BikeWeights {
+    fn default() -> Self {
+        Self {
+            distance: 1.0,
+            climb: 8.0,
+            surface: 1.0,
+        }
+    }
+}
+
+//osm node id -> metres above sea level, csv with id,elevation columns
+pub fn read_elevations(file_path: &str) -> Result<HashMap<u64, f64>, Box<dyn Error>> {
+    let file = File::open(file_path)?;
+    let mut rdr = ReaderBuilder::new().from_reader(file);
+    let mut elevations = HashMap::new();
+    for result in rdr.deserialize::<(u64, f64)>() {
+        let (id, elevation) = result?;
+        elevations.insert(id, elevation);
+    }
+    Ok(elevations)
+}
+
+//osm way id -> surface, csv with osm_id,surface columns
+pub fn read_surfaces(file_path: &str) -> Result<HashMap<String, Surface>, Box<dyn Error>> {
+    let file = File::open(file_path)?;
+    let mut rdr = ReaderBuilder::new().from_reader(file);
+    let mut surfaces = HashMap::new();
+    for result in rdr.deserialize::<(String, String)>() {
+        let (osm_id, surface) = result?;
+        surfaces.insert(osm_id, Surface::from_tag(&surface));
+    }
+    Ok(surfaces)
+}
+
+#[derive(Debug, Clone)]
+pub struct BikeRoute {
+    pub route: Route,
+    //metres climbed
+    pub ascent: f64,
+    //weighted cost under the model that found it
+    pub cost: f64,
+}
+
+//per edge climb and surface, looked up by the arcs of a bike Router built from the same Graph
+#[derive(Debug, Clone)]
+pub struct BikeCostModel {
+    pub weights: BikeWeights,
+    //(climbed travelling forward, climbed travelling backward) per Graph edge, from the endpoint elevations
+    pub ascent: Vec<(f64, f64)>,
+    pub surfaces: Vec<Surface>,
+}
+
+impl BikeCostModel {
+    //nodes without an elevation are treated as flat, ways without a surface as Unknown
+    pub fn new(graph: &Graph, elevations: &HashMap<u64, f64>, surfaces: &HashMap<String, Surface>, weights: BikeWeights) -> Self {
+        let elevation = |id: &str| id.parse::<u64>().ok().and_then(|id| elevations.get(&id)).copied();
+        let ascent = graph.edges.iter().map(|edge| {
+            match (elevation(&edge.source), elevation(&edge.target)) {
+                (Some(source), Some(target)) => ((target - source).max(0.0), (source - target).max(0.0)),
+                _ => (0.0, 0.0),
+            }
+        }).collect();
+        let surfaces = graph.edges.iter()
+            .map(|edge| surfaces.get(&edge.osm_id).copied().unwrap_or(Surface::Unknown))
+            .collect();
+        Self { weights, ascent, surfaces }
+    }
+
+    pub fn ascent(&self, arc: &Arc) -> f64 {
+        let (forward, backward) = self.ascent[arc.edge];
+        if arc.forward { forward } else { backward }
+    }
+
+    pub fn cost(&self, arc: &Arc) -> f64 {
+        let weights = &self.weights;
+        arc.length * (weights.distance + weights.surface * self.surfaces[arc.edge].penalty()) + weights.climb * self.ascent(arc)
+    }
+
+    fn to_bike_route(&self, path: Vec<Arc>) -> BikeRoute {
+        let ascent = path.iter().map(|arc| self.ascent(arc)).sum();
+        let cost = path.iter().map(|arc| self.cost(arc)).sum();
+        BikeRoute { route: Router::to_route(path), ascent, cost }
+    }
+
+    //cheapest route under the weights
+    pub fn route(&self, router: &Router, source: usize, target: usize) -> Option<BikeRoute> {
+        let search = router.dijkstra(&[(source, 0.0)], Some(target), f64::INFINITY, |arc| self.cost(arc));
+        if !search.reached(target) {
+            return None;
+        }
+        Some(self.to_bike_route(search.path_to(target)))
+    }
+
+    //every route not beaten on both distance and ascent by another, shortest first
+    pub fn pareto_routes(&self, router: &Router, source: usize, target: usize) -> Vec<BikeRoute> {
+        // labels are settled in (distance, ascent) order, so one is dominated exactly when an earlier settled label at its node climbed no more
+        let mut labels: Vec<Label> = vec![Label { node: source, parent: None }];
+        let mut best_ascent = vec![f64::INFINITY; router.node_count()];
+        let mut heap = BinaryHeap::new();
+        heap.push(ParetoState { distance: 0.0, ascent: 0.0, label: 0 });
+        let mut found: Vec<usize> = Vec::new();
+
+        while let Some(ParetoState { distance, ascent, label }) = heap.pop() {
+            let node = labels[label].node;
+            if ascent > best_ascent[node] - ASCENT_EPSILON || ascent > best_ascent[target] - ASCENT_EPSILON {
+                continue;
+            }
+            best_ascent[node] = ascent;
+            if node == target {
+                found.push(label);
+                continue;
+            }
+            for arc in &router.arcs[node] {
+                let next_ascent = ascent + self.ascent(arc);
+                if next_ascent > best_ascent[arc.head] - ASCENT_EPSILON || next_ascent > best_ascent[target] - ASCENT_EPSILON {
+                    continue;
+                }
+                labels.push(Label { node: arc.head, parent: Some((label, *arc)) });
+                heap.push(ParetoState { distance: distance + arc.length, ascent: next_ascent, label: labels.len() - 1 });
+            }
+        }
+
+        found.into_iter().map(|label| {
+            let mut path = Vec::new();
+            let mut current = label;
+            while let Some((parent, arc)) = labels[current].parent {
+                path.push(arc);
+                current = parent;
+            }
+            path.reverse();
+            self.to_bike_route(path)
+        }).collect()
+    }
+}
+
+struct Label {
+    node: usize,
+    //(previous label, arc taken from it)
+    parent: Option<(usize, Arc)>,
+}
+
+//ordered so BinaryHeap pops the shortest label first, then the one that climbed least
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct ParetoState {
+    distance: f64,
+    ascent: f64,
+    label: usize,
+}
+
+impl Eq for ParetoState {}
+
+impl Ord for ParetoState {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.distance.total_cmp(&self.distance)
+            .then_with(|| other.ascent.total_cmp(&self.ascent))
+            .then_with(|| self.label.cmp(&other.label))
+    }
+}
+
+impl PartialOrd for ParetoState {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use super::{BikeCostModel, BikeWeights, Surface};
+    use crate::graph::fixtures::paths;
+    use crate::graph::{Graph, Node};
+    use crate::routing::{Profile, Router};
+    use approx::assert_relative_eq;
+
+    // 1 to 4 over the hill at 2 (short), around it through 3 (longer, flat) or along the unpaved 5 (longest, flat)
+    fn hill() -> Graph {
+        let nodes = [
+            Node::new(1, -118.0, 34.0),
+            Node::new(2, -118.0, 34.001),
+            Node::new(3, -117.999, 34.001),
+            Node::new(4, -118.0, 34.002),
+            Node::new(5, -118.002, 34.001),
+        ];
+        let mut graph = paths(&nodes, &[("12", 0, 1, 100.0), ("24", 1, 3, 100.0), ("13", 0, 2, 150.0), ("34", 2, 3, 150.0), ("15", 0, 4, 120.0), ("54", 4, 3, 120.0)]);
+        for edge in &mut graph.edges {
+            edge.foot = false;
+        }
+        graph
+    }
+
+    #[test]
+    fn test_weights_and_pareto_set() {
+        let graph = hill();
+        let router = Router::new(&graph, Profile::Bike);
+        let elevations: HashMap<u64, f64> = [(1, 10.0), (2, 40.0), (3, 12.0), (4, 10.0), (5, 10.0)].into_iter().collect();
+        let surfaces: HashMap<String, Surface> = [("15", "gravel"), ("54", "dirt"), ("12", "asphalt"), ("24", "asphalt"), ("13", "asphalt"), ("34", "asphalt")]
+            .into_iter().map(|(id, tag)| (id.to_string(), Surface::from_tag(tag))).collect();
+        let (source, target) = (router.node_index(1).unwrap(), router.node_index(4).unwrap());
+
+        let flat = BikeCostModel::new(&graph, &elevations, &surfaces, BikeWeights { climb: 0.0, surface: 0.0, ..Default::default() });
+        assert_eq!(flat.route(&router, source, target).unwrap().route.edge_ids(&graph), vec!["12", "24"]);
+        let model = BikeCostModel::new(&graph, &elevations, &surfaces, BikeWeights::default());
+        let best = model.route(&router, source, target).unwrap();
+        assert_eq!(best.route.edge_ids(&graph), vec!["13", "34"]);
+        assert_relative_eq!(best.ascent, 2.0);
+        assert_relative_eq!(best.cost, 300.0 + 8.0 * 2.0);
+
+        let pareto = model.pareto_routes(&router, source, target);
+        let summary: Vec<(f64, f64)> = pareto.iter().map(|route| (route.route.distance, route.ascent)).collect();
+        assert_eq!(summary, vec![(200.0, 30.0), (240.0, 0.0)]);
+        assert_eq!(pareto[1].route.edge_ids(&graph), vec!["15", "54"]);
+    }
+}
@@ -0,0 +1,143 @@
+// using geographiclib_rs because geographiclib doesnt provide the m12 and M12 required by Karney's improvements to BML
+use core::fmt;
+use geographiclib_rs::{Geodesic, InverseGeodesic, DirectGeodesic};
+
+/*
+ * primarily a translation of the python code provided in the link below into rust
+ * https://sourceforge.net/p/geographiclib/discussion/1026621/thread/21aaff9f/?page=2&limit=25#766f
+ */
+
+// value of semi major axis in WGS84 according to library source code since
+// geod.a is a private member
+const R: f64 = 6378137.0;
+// the iteration converges in a handful of steps, give up rather than spin on degenerate input
+const MAX_ITERATIONS: u32 = 50;
+ 
+#[derive(Debug)]
+pub struct Intercept {
+    pub lat: f64,
+    pub lon: f64,
+    pub dist: f64,
+    pub dir: f64,
+}
+
+pub struct DMS {
+    pub is_neg: bool,
+    pub deg: u8,
+    pub min: u8,
+    pub sec: f64,
+}
+
+impl fmt::Display for DMS {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        if self.is_neg {
+            write!(f, "-{}° {}\' {:.4}\"", self.deg, self.min, self.sec)
+        } else {
+            write!(f, "{}° {}\' {:.4}\"", self.deg, self.min, self.sec)
+        }
+    }
+}
+
+pub fn dd_to_dms(degs: f64) -> DMS {
+    let decimal_deg = degs.abs();
+    let decimal_min = (decimal_deg - decimal_deg.floor()) * 60.0;
+    let decimal_sec = (decimal_min - decimal_min.floor()) * 60.0;
+
+    DMS {
+        is_neg: degs < 0.0,
+        deg: decimal_deg as u8,
+        min: decimal_min as u8,
+        sec: decimal_sec,
+    }
+}
+
+//closest point, shortest distance, and heading are returned as part of Intercept struct
+pub fn point_to_geodesic(mut p_a: (f64, f64), p_b: (f64, f64), p_p: (f64, f64), debug: bool) -> Intercept {
+    let geod = Geodesic::wgs84();
+    let mut iter_num = 0;
+    let mut s_ax: f64;
+    loop {
+        /* 
+         * the 7-tuple gives us (in order):
+         * s12, azi1, azi2, m12, M12, M21, a12
+         * from the library source code (around line 1130 in geodesic.rs as of 
+         * f8d9f98), there is no way to get m12 and M12 without a12
+         * https://github.com/georust/geographiclib-rs/blob/main/src/geodesic.rs#L1096
+         */ 
+        let (s_ap, azi1_ap, _, m_ap, mm_ap, _, _) =
+            geod.inverse(p_a.0, p_a.1, p_p.0, p_p.1);
+        // the 3-tuple gives: azi1, azi2, a12
+        let (azi1_ab, _, _) =
+            geod.inverse(p_a.0, p_a.1, p_b.0, p_b.1);
+        // a point on the geodesic is its own intercept, m_ap / s_ap below would be NaN and never converge
+        if s_ap == 0.0 || iter_num == MAX_ITERATIONS {
+            return Intercept{lat: p_a.0, lon: p_a.1, dist: s_ap, dir: azi1_ab};
+        }
+        let a = azi1_ap - azi1_ab;
+        s_ax = m_ap * a.to_radians().cos() / ((m_ap / s_ap) * a.to_radians().cos().powi(2) + mm_ap * a.to_radians().sin().powi(2));
+        if iter_num == 0 {
+            s_ax = R * ((s_ap / R).sin() * a.to_radians().cos()).atan2((s_ap / R).cos());
+        }
+        
+        let (p_a2_lat2, p_a2_lon2) = geod.direct(p_a.0, p_a.1, azi1_ab, s_ax);
+        if debug {
+            eprintln!("{}, {}, {}, {:.4}", iter_num + 1, dd_to_dms(p_a2_lat2), dd_to_dms(p_a2_lon2), s_ax)
+        }
+        if s_ax.abs() < 1e-2 {
+            return Intercept{lat: p_a.0, lon: p_a.1, dist: s_ap, dir: azi1_ab};
+        }
+        p_a = (p_a2_lat2, p_a2_lon2);
+        iter_num += 1;
+   }
+}
+
+/*
+//the closest point on the line from distance 
+fn closest_point_geodesic(p_a: (f64, f64), p_b: (f64, f64), p_p: (f64, f64)) -> (f64, f64) {
+    let result: Intercept = point_to_geodesic(p_a, p_b, p_p, false);
+    (result.lat, result.lon)
+}
+
+//shortest distance to that line from that point
+fn shortest_distance_geodesic(p_a: (f64, f64), p_b: (f64, f64), p_p: (f64, f64)) -> f64 {
+    let result: Intercept = point_to_geodesic(p_a, p_b, p_p, false);
+    result.dist
+}
+
+fn heading(p_a: (f64, f64), p_b: (f64, f64), p_p: (f64, f64)) -> f64 {
+	let geod = Geodesic::wgs84();
+    let intercept_point = closest_point_geodesic(p_a, p_b, p_p);
+    let (dir, _, _) = geod.inverse(p_a.0, p_a.1, intercept_point.0, intercept_point.1);
+    dir
+}
+*/
+
+//the distance of line segments if the line was cut at where point is --> calculate distance from endpoint A to intercept, then distance from intercept to endpoint B
+pub fn geodesic_segments(p_a: (f64, f64), p_b: (f64, f64), intercept_point: (f64, f64)) -> (f64, f64) {
+	let geod = Geodesic::wgs84();
+    let seg_a = geod.inverse(p_a.0, p_a.1, intercept_point.0, intercept_point.1);
+    let seg_b = geod.inverse(intercept_point.0, intercept_point.1, p_b.0, p_b.1);
+    (seg_a, seg_b)
+}
+
+//closest point to p_p on the segment from p_a to p_b rather than the whole geodesic, with its distance from p_a
+pub fn point_to_segment(p_a: (f64, f64), p_b: (f64, f64), p_p: (f64, f64)) -> (Intercept, f64) {
+    let geod = Geodesic::wgs84();
+    let length: f64 = geod.inverse(p_a.0, p_a.1, p_b.0, p_b.1);
+    if length == 0.0 {
+        let (dist, dir, _, _) = geod.inverse(p_a.0, p_a.1, p_p.0, p_p.1);
+        return (Intercept{lat: p_a.0, lon: p_a.1, dist, dir}, 0.0);
+    }
+    let intercept = point_to_geodesic(p_a, p_b, p_p, false);
+    let (seg_a, seg_b) = geodesic_segments(p_a, p_b, (intercept.lat, intercept.lon));
+    // an intercept past either end is further from the other end than the segment is long
+    let (end, along) = if seg_b > length && seg_b > seg_a {
+        (p_a, 0.0)
+    } else if seg_a > length {
+        (p_b, length)
+    } else {
+        return (intercept, seg_a);
+    };
+    let dist: f64 = geod.inverse(end.0, end.1, p_p.0, p_p.1);
+    (Intercept{lat: end.0, lon: end.1, dist, dir: intercept.dir}, along)
+}
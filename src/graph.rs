@@ -1,29 +1,151 @@
-use std::{fs::File, collections::{HashMap, HashSet}, thread, sync::{Arc, Mutex}};
+use std::{fs::File, collections::{BTreeSet, HashMap, HashSet}, error::Error, thread, sync::{Arc, Mutex}};
 use geographiclib_rs::{Geodesic, InverseGeodesic};
 use gtfs_structures::DirectionType::Outbound;
-use chrono::{DateTime, Local};
+use chrono::{Duration, NaiveDate, NaiveDateTime};
 use csv::{ReaderBuilder, StringRecord};
 use gtfs_structures::DirectionType;
 use serde::{Serialize, Deserialize};
 use tokio_postgres::Client;
 use vpsearch::{MetricSpace, BestCandidate};
+use crate::calendar::Calendar;
+use crate::interner::NodeInterner;
+use crate::geodesic::point_to_segment;
+use crate::transfers::{Level, Pathway, StationRules, TransferRule, TransferType};
 
+//metres per second for walking transfers between stops
+pub const TRANSFER_WALK_SPEED: f64 = 1.4;
 
-#[derive(Serialize, Deserialize, Debug)]
+// metres a stop may be further from the shape than from its closest point on it and still match an earlier pass
+const SHAPE_MATCH_SLACK: f64 = 20.0;
+// parent_station levels followed up from a stop, a feed has at most boarding area -> platform -> station
+const MAX_PARENT_DEPTH: usize = 8;
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct GTFSGraph {
     pub onestop_id: String,
     pub old_services: Vec<String>,
     pub route_names: HashMap<String, String>,
-    //in the future add child stops
+    //stops served by the timetable, stations and other locations are in locations
     pub stops: Vec<GTFSNode>,
     pub stop_names: HashMap<String, String>,
-    //HashMap<(start_stop, end_stop), HashSet<edge_weights>>
-    pub edges: HashMap<(String, String), HashSet<u32>>,
-    //<route id, <stop id, <service id, Vec<stop time,trip_id>>>>
-    pub routes: HashMap<String, HashMap<String, HashMap<String, Vec<(String, String)>>>>,
+    //<(start_stop, end_stop), hop>
+    pub edges: HashMap<(String, String), GTFSEdge>,
+    //<route id, <stop id, <service id-direction, Vec<(arrival, departure, trip_id)>>>>, seconds since midnight
+    pub routes: HashMap<String, HashMap<String, HashMap<String, Vec<(u32, u32, String)>>>>,
+    //<trip id, trip>, stop times in stop_sequence order for journey planning
+    #[serde(default)]
+    pub trips: HashMap<String, GTFSTrip>,
+    //service days for every service in the feed, not only the ones kept
+    #[serde(default)]
+    pub calendar: Calendar,
+    //onestop ids of the feeds merged in, ids are prefixed "onestop_id:" once namespaced
+    #[serde(default)]
+    pub feeds: Vec<String>,
+    //<(from stop, to stop), seconds> a rider can walk between stops, a stop to itself is the minimum time to change there
+    #[serde(default)]
+    pub transfers: HashMap<(String, String), u32>,
+    //transfers.txt, pathways.txt and levels.txt as read, rows naming a station stand once for each of its platforms.
+    //route and trip specific rules only live here, ruled_transfers lists the changes they cover
+    #[serde(default)]
+    pub station_rules: StationRules,
+    //<stop id, location> for every row of stops.txt
+    #[serde(default)]
+    pub locations: HashMap<String, GTFSLocation>,
+    //<parent id, child ids>, platforms, entrances and nodes of a station or boarding areas of a platform
+    #[serde(default)]
+    pub children: HashMap<String, Vec<String>>,
+    //<trip id, trip_headsign> for the trips that have one
+    #[serde(default)]
+    pub headsigns: HashMap<String, String>,
+    //<stop id, (trip id, index into its stop_times)> for every stop a trip leaves from
+    #[serde(default)]
+    pub stop_trips: HashMap<String, Vec<(String, usize)>>,
+}
+
+//location_type in stops.txt
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LocationType {
+    //0 or empty, where vehicles stop
+    Platform,
+    Station,
+    Entrance,
+    GenericNode,
+    BoardingArea,
+}
+
+impl LocationType {
+    pub fn from_gtfs(location_type: gtfs_structures::LocationType) -> Self {
+        match location_type {
+            gtfs_structures::LocationType::StopArea => LocationType::Station,
+            gtfs_structures::LocationType::StationEntrance => LocationType::Entrance,
+            gtfs_structures::LocationType::GenericNode => LocationType::GenericNode,
+            gtfs_structures::LocationType::BoardingArea => LocationType::BoardingArea,
+            _ => LocationType::Platform,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct GTFSLocation {
+    pub location_type: LocationType,
+    //parent_station, the platform for a boarding area
+    pub parent: Option<String>,
+    pub lon: Option<f64>,
+    pub lat: Option<f64>,
+}
+
+//a trip leaving one of a station's platforms
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct StationDeparture {
+    //seconds since midnight
+    pub departure: u32,
+    pub route_id: String,
+    pub trip_id: String,
+    //platform the trip leaves from
+    pub stop_id: String,
+    //last stop of the trip
+    pub destination: String,
+}
+
+//a row of a departure board
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct NextDeparture {
+    //agency local time the trip leaves
+    pub time: NaiveDateTime,
+    //day the trip's times count from, the day before time for trips running past midnight
+    pub service_date: NaiveDate,
+    pub route_id: String,
+    pub route_name: String,
+    pub trip_id: String,
+    //trip_headsign, else the name of the last stop
+    pub headsign: String,
+    //platform the trip leaves from
+    pub stop_id: String,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+//(stop id, arrival, departure), seconds since midnight
+pub type StopTimes = Vec<(String, u32, u32)>;
+//(stop id, arrival, departure, distance along the trip) as in stop_times.txt, where either time may be missing
+pub type UntimedStopTimes = Vec<(String, Option<u32>, Option<u32>, Option<f64>)>;
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct GTFSTrip {
+    pub route_id: String,
+    pub service_id: String,
+    //(stop id, arrival, departure), seconds since midnight
+    pub stop_times: Vec<(String, u32, u32)>,
+}
+
+//hop between consecutive stops of some trips
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct GTFSEdge {
+    //seconds between the two stops on the trips making the hop
+    pub weights: HashSet<u32>,
+    //(lon, lat) along the trip shape from one stop to the next, empty when none of the trips has a shape
+    pub geometry: Vec<(f64, f64)>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct GTFSNode {
     pub id: String,
     pub lon: f64,
@@ -41,7 +163,7 @@ impl vpsearch::MetricSpace for GTFSNode {
 }
 
 
-struct RadiusBasedNeighborhood<Item: MetricSpace<Impl>, Impl> {
+pub struct RadiusBasedNeighborhood<Item: MetricSpace<Impl>, Impl> {
     max_distance: Item::Distance,
     ids: HashSet<usize>,
 }
@@ -49,7 +171,7 @@ struct RadiusBasedNeighborhood<Item: MetricSpace<Impl>, Impl> {
 impl<Item: MetricSpace<Impl>, Impl> RadiusBasedNeighborhood<Item, Impl> {
     /// Helper function for creating the RadiusBasedNeighborhood struct.
     /// Here `max_distance` is an exclusive upper bound to the euclidean distance.
-    fn new(max_distance: Item::Distance) -> Self {
+    pub fn new(max_distance: Item::Distance) -> Self {
         RadiusBasedNeighborhood {
             max_distance,
             ids: HashSet::<usize>::new(),
@@ -97,28 +219,48 @@ impl GTFSGraph {
             stop_names: HashMap::new(),
             edges: HashMap::new(),
             stops: Vec::new(),
+            trips: HashMap::new(),
+            calendar: Calendar::default(),
+            feeds: vec![onestop_id.to_string()],
+            transfers: HashMap::new(),
+            station_rules: StationRules::default(),
+            locations: HashMap::new(),
+            children: HashMap::new(),
+            headsigns: HashMap::new(),
+            stop_trips: HashMap::new(),
         }
     }
 
+    //rows go under the feed each route came from, with the ids as that feed has them
     pub async fn to_sql(&mut self, client: &Client) {
         for (route, stops) in &self.routes {
+            let (onestop_id, route) = self.feed_of(route);
             // Iterate over the middle HashMap
             for (stop, services) in stops {
+                let stop = self.feed_of(stop).1;
                 // Iterate over the innermost HashMap
                 for (service, times) in services {
-                    let timetable = serde_json::to_string(&times.into_iter().map(|(first, _)| first).collect::<Vec<_>>());
-                    let trips = serde_json::to_string(&times.into_iter().map(|(_, last)| last).collect::<Vec<_>>());
+                    let service = self.feed_of(service).1;
+                    let timetable = serde_json::to_string(&times.iter().map(|(arrival, _, _)| format_time(*arrival)).collect::<Vec<_>>());
+                    let trips = serde_json::to_string(&times.iter().map(|(_, _, trip_id)| self.feed_of(trip_id).1).collect::<Vec<_>>());
                     // Prepare the SQL statement with parameterized query
                     let service_id: String = service[0..service.len() - 2].to_string();
                     let direction: String = service[service.len() - 1..].to_string();
                     let statement = "INSERT INTO timetable(id, onestop_id, route, stop, service, direction, trip_id, time) VALUES ($1, $2, $3, $4, $5, $6, $7, $8) RETURNING *";
-                    let _ = client.query_one(statement, &[&format!("{}-{}-{}-{}", self.onestop_id, route, stop, service), &self.onestop_id, &route, &stop, &service_id,  &direction, &trips.unwrap(), &timetable.unwrap()]).await;
+                    let _ = client.query_one(statement, &[&format!("{}-{}-{}-{}", onestop_id, route, stop, service), &onestop_id, &route, &stop, &service_id,  &direction, &trips.unwrap(), &timetable.unwrap()]).await;
                     //println!("{:#?}", rows)
                 }
             }
         }
     }
 
+    //the onestop id of the feed an id came from and the id within that feed, namespaced ids start with "onestop_id:"
+    pub fn feed_of<'a>(&'a self, id: &'a str) -> (&'a str, &'a str) {
+        self.feeds.iter()
+            .find_map(|feed| Some((feed.as_str(), id.strip_prefix(feed.as_str())?.strip_prefix(':')?)))
+            .unwrap_or((&self.onestop_id, id))
+    }
+
     async fn from_sql(&mut self, client: &Client) {
 
     }
@@ -144,46 +286,147 @@ impl GTFSGraph {
         }
     }
 
-    pub fn add_stoptime(&mut self, id: String, stop_id: String, service_id: String, arrival_time: u32, direction_id: DirectionType, trip_id: String) {//, start_date: &String, end_date: &String) {
+    pub fn add_location(&mut self, id: String, name: String, location_type: LocationType, parent: Option<String>, lat: Option<f64>, lon: Option<f64>) {
+        self.stop_names.entry(id.clone()).or_insert(name);
+        if let Some(parent) = &parent {
+            self.children.entry(parent.clone()).or_default().push(id.clone());
+        }
+        self.locations.insert(id, GTFSLocation { location_type, parent, lon, lat });
+    }
+
+    //the station a stop belongs to, the stop itself when it has none. parents looping in a broken feed give up
+    //after a few levels
+    pub fn station<'a>(&'a self, stop_id: &'a str) -> &'a str {
+        let mut current = stop_id;
+        // boarding area -> platform -> station
+        for _ in 0..MAX_PARENT_DEPTH {
+            match self.locations.get(current) {
+                Some(GTFSLocation { parent: Some(parent), location_type, .. }) if *location_type != LocationType::Station => current = parent,
+                _ => break,
+            }
+        }
+        current
+    }
+
+    fn children_of(&self, station: &str, location_type: LocationType) -> Vec<&str> {
+        let mut children: Vec<&str> = self.children.get(station).into_iter().flatten()
+            .filter(|child| self.locations.get(*child).is_some_and(|location| location.location_type == location_type))
+            .map(|child| child.as_str())
+            .collect();
+        children.sort();
+        children
+    }
+
+    pub fn platforms(&self, station: &str) -> Vec<&str> {
+        self.children_of(station, LocationType::Platform)
+    }
+
+    pub fn entrances(&self, station: &str) -> Vec<&str> {
+        self.children_of(station, LocationType::Entrance)
+    }
+
+    //departures of the loaded trips from every platform of the station the stop belongs to, earliest first
+    pub fn departures(&self, stop_id: &str) -> Vec<StationDeparture> {
+        let station = self.station(stop_id);
+        // the stop, its station and what is under that short of another station: platforms and their boarding areas
+        let mut stops: BTreeSet<&str> = BTreeSet::from([stop_id, station]);
+        let mut parents = vec![station];
+        while let Some(parent) = parents.pop() {
+            for child in self.children.get(parent).into_iter().flatten() {
+                let nested = self.locations.get(child).is_some_and(|location| location.location_type == LocationType::Station);
+                if !nested && stops.insert(child) {
+                    parents.push(child);
+                }
+            }
+        }
+        let mut departures = Vec::new();
+        for stop in stops {
+            for (trip_id, position) in self.stop_trips.get(stop).into_iter().flatten() {
+                let Some(trip) = self.trips.get(trip_id) else {
+                    continue;
+                };
+                let (last, _, _) = &trip.stop_times[trip.stop_times.len() - 1];
+                departures.push(StationDeparture { departure: trip.stop_times[*position].2, route_id: trip.route_id.clone(), trip_id: trip_id.clone(), stop_id: stop.to_string(), destination: last.clone() });
+            }
+        }
+        departures.sort_by(|a, b| (a.departure, &a.trip_id).cmp(&(b.departure, &b.trip_id)));
+        departures
+    }
+
+    //the next count departures from the stop, or every platform of its station, at or after the agency local time
+    //at. trips count on the days their service runs, so the previous day's still running past midnight are in and
+    //tomorrow's early ones follow late at night. only the services loaded are seen, from_file_between should cover
+    //the day before and after
+    pub fn next_departures(&self, stop_id: &str, at: NaiveDateTime, count: usize) -> Vec<NextDeparture> {
+        let departures = self.departures(stop_id);
+        let mut board = Vec::new();
+        for days in -1..=1 {
+            let service_date = at.date() + Duration::days(days);
+            let midnight = service_date.and_hms_opt(0, 0, 0).unwrap();
+            for departure in &departures {
+                let trip = &self.trips[&departure.trip_id];
+                let time = midnight + Duration::seconds(departure.departure as i64);
+                if time < at || !self.calendar.runs(&trip.service_id, service_date) {
+                    continue;
+                }
+                let headsign = self.headsigns.get(&departure.trip_id)
+                    .or_else(|| self.stop_names.get(&departure.destination))
+                    .unwrap_or(&departure.destination);
+                board.push(NextDeparture {
+                    time,
+                    service_date,
+                    route_id: departure.route_id.clone(),
+                    route_name: self.route_names.get(&departure.route_id).cloned().unwrap_or_else(|| departure.route_id.clone()),
+                    trip_id: departure.trip_id.clone(),
+                    headsign: headsign.clone(),
+                    stop_id: departure.stop_id.clone(),
+                });
+            }
+        }
+        board.sort_by(|a, b| (a.time, &a.trip_id).cmp(&(b.time, &b.trip_id)));
+        board.truncate(count);
+        board
+    }
+
+    pub fn add_stoptime(&mut self, id: String, stop_id: String, service_id: String, arrival_time: u32, departure_time: u32, direction_id: DirectionType, trip_id: String) {
         if self.old_services.contains(&service_id) {
             return;
         }
         if !self.routes.contains_key(&id) {
             self.add_route(id.clone(), "Kyler's Transit Line".to_string());
         }
-        let mut arrival_string = (arrival_time/3600).to_string();
-        if arrival_string.len() == 1 {
-            arrival_string.insert_str(0, "0");
-        }
-        if ((arrival_time % 3600)/60).to_string().as_str().len() == 1 {
-            arrival_string.push_str(format!(":0{}", ((arrival_time % 3600)/60).to_string()).as_str());
-        } else {
-            arrival_string.push_str(format!(":{}", ((arrival_time % 3600)/60).to_string()).as_str());
-        }
-        if !self.routes.get_mut(&id).unwrap().contains_key(&stop_id) {
-            self.routes.get_mut(&id).unwrap().insert(stop_id.clone(), HashMap::new());
-        }
-        let mut direction = format!("{:?}", direction_id);
-        if direction == "Outbound" {
-            direction = 0.to_string();
-        } else {
-            direction = 1.to_string();
+        let direction = if direction_id == Outbound { 0 } else { 1 };
+        self.routes.get_mut(&id).unwrap()
+            .entry(stop_id).or_default()
+            .entry(format!("{}-{}", service_id, direction)).or_default()
+            .push((arrival_time, departure_time, trip_id));
+    }
+
+    pub fn add_trip(&mut self, id: String, route_id: String, service_id: String, stop_times: Vec<(String, u32, u32)>) {
+        if self.old_services.contains(&service_id) || stop_times.len() < 2 {
+            return;
         }
-        if !self.routes.get_mut(&id).unwrap().get_mut(&stop_id).unwrap().contains_key(&format!("{}-{}", service_id, direction)) {
-            let new_stop_times =vec![(arrival_string, trip_id)];
-            self.routes.get_mut(&id).unwrap().get_mut(&stop_id).unwrap().insert(format!("{}-{}", service_id, direction), new_stop_times);
-        } else {
-            self.routes.get_mut(&id).unwrap().get_mut(&stop_id).unwrap().get_mut(&format!("{}-{}", service_id, direction)).unwrap().push((arrival_string, trip_id));
+        // nobody boards at the last stop
+        for (position, (stop, _, _)) in stop_times[..stop_times.len() - 1].iter().enumerate() {
+            self.stop_trips.entry(stop.clone()).or_default().push((id.clone(), position));
         }
+        self.trips.insert(id, GTFSTrip { route_id, service_id, stop_times });
     }
 
     pub fn add_edge(&mut self, stop1: String, arrival1: u32, stop2: String, arrival2: u32) {
-        let edge_weight = arrival2 - arrival1;
-        if self.edges.contains_key(&(stop1.clone(), stop2.clone())) && !self.edges.get(&(stop1.clone(), stop2.clone())).as_ref().unwrap().contains(&edge_weight) {
-            self.edges.get_mut(&(stop1.clone(), stop2.clone())).unwrap().insert(edge_weight);
-        } else {
-            self.edges.insert((stop1.clone(), stop2.clone()), HashSet::new());
-            self.edges.get_mut(&(stop1, stop2)).unwrap().insert(edge_weight);
+        self.edges.entry((stop1, stop2)).or_default().weights.insert(arrival2 - arrival1);
+    }
+
+    //cut a shape, (lon, lat) points, into the hops between the trip's stops and keep each piece on its stop pair
+    //edge unless another shape got there first
+    pub fn add_shape(&mut self, shape: &[(f64, f64)], stops: &[(String, f64, f64)]) {
+        let points: Vec<(f64, f64)> = stops.iter().map(|(_, lon, lat)| (*lon, *lat)).collect();
+        for (hop, geometry) in stops.windows(2).zip(shape_segments(shape, &points)) {
+            if let Some(edge) = self.edges.get_mut(&(hop[0].0.clone(), hop[1].0.clone())) {
+                if edge.geometry.is_empty() {
+                    edge.geometry = geometry;
+                }
+            }
         }
     }
 
@@ -191,61 +434,502 @@ impl GTFSGraph {
         for route in &mut self.routes {
             for stop in route.1 {
                 for service in stop.1 {
-                    service.1.sort_by(|a, b| a.0.cmp(&b.0));
+                    service.1.sort();
                 }
             }
         }
     }
 
+    //services running today in the agency's timezone
     pub fn from_file(file: &str, onestop_id: &str) -> Self {
         let gfts_rail = gtfs_structures::Gtfs::new(file).unwrap();
+        let today = Calendar::from_gtfs(&gfts_rail).today().unwrap_or_else(|error| panic!("{}: {}", file, error));
+        let mut graph = Self::from_gtfs(gfts_rail, onestop_id, today, today);
+        graph.load_station_rules(file);
+        graph
+    }
+
+    //services running on any day from start to end inclusive. trips don't keep their dates, route on one
+    //service_day at a time or trips of different days chain together
+    pub fn from_file_between(file: &str, onestop_id: &str, start: NaiveDate, end: NaiveDate) -> Self {
+        let gfts_rail = gtfs_structures::Gtfs::new(file).unwrap();
+        let mut graph = Self::from_gtfs(gfts_rail, onestop_id, start, end);
+        graph.load_station_rules(file);
+        graph
+    }
+
+    fn from_gtfs(gfts_rail: gtfs_structures::Gtfs, onestop_id: &str, start: NaiveDate, end: NaiveDate) -> Self {
         let mut graph: GTFSGraph = GTFSGraph::new(onestop_id); 
+        graph.calendar = Calendar::from_gtfs(&gfts_rail);
         for route in gfts_rail.routes {
             graph.add_route(route.1.id, route.1.long_name);
         }
-        let local: DateTime<Local> = Local::now();
-        let formatted_date = local.format("%Y-%m-%d").to_string();
-        //let mut future_services: Vec<String> = Vec::new();
-        //let mut services: Vec<String> = Vec::new();
-        for service in gfts_rail.calendar {
-            if service.1.end_date.to_string() <= formatted_date {
-                /*if service.1.start_date.to_string() >= formatted_date {
-                    graph.exclude_service(service.1.id.clone());
-                } else {
-                    graph.exclude_service(service.1.id.clone());
-                }*/
-                graph.exclude_service(service.1.id.clone());
+        let running = graph.calendar.services_between(start, end);
+        let services: HashSet<&String> = gfts_rail.trips.values().map(|trip| &trip.service_id).collect();
+        for service in services {
+            if !running.contains(service) {
+                graph.exclude_service(service.clone());
             }
-            //eprintln!("{} {} {} {} {} ", formatted_date, formatted_date <= service.1.start_date.to_string(), service.1.start_date.to_string(), formatted_date <= service.1.end_date.to_string(), service.1.end_date.to_string());
         }
 
+        // (shape id, stops in order) cut once all trips are in, in shape id order to pick the same shape for a hop every time
+        let mut shaped: BTreeSet<(String, Vec<String>)> = BTreeSet::new();
         for trip in gfts_rail.trips {
-            let mut last_stop: Option<String> = None;
-            let mut last_arrival: Option<u32> = None;
-            for stop_times in trip.1.stop_times {
+            if let Some(shape_id) = &trip.1.shape_id {
+                shaped.insert((shape_id.clone(), trip.1.stop_times.iter().map(|stop_time| stop_time.stop.id.clone()).collect()));
+            }
+            // distance along the trip to place untimed stops by, from the feed when every stop has one
+            let along: Vec<Option<f64>> = if trip.1.stop_times.iter().all(|stop_time| stop_time.shape_dist_traveled.is_some()) {
+                trip.1.stop_times.iter().map(|stop_time| stop_time.shape_dist_traveled.map(f64::from)).collect()
+            } else {
+                let located: Option<Vec<(f64, f64)>> = trip.1.stop_times.iter().map(|stop_time| Some((stop_time.stop.longitude?, stop_time.stop.latitude?))).collect();
+                match located {
+                    Some(located) => {
+                        let geod = Geodesic::wgs84();
+                        let mut travelled = 0.0;
+                        located.iter().enumerate().map(|(i, (lon, lat))| {
+                            if i > 0 {
+                                let distance: f64 = geod.inverse(located[i - 1].1, located[i - 1].0, *lat, *lon);
+                                travelled += distance;
+                            }
+                            Some(travelled)
+                        }).collect()
+                    }
+                    None => vec![None; trip.1.stop_times.len()],
+                }
+            };
+            let mut untimed: UntimedStopTimes = Vec::new();
+            for (stop_times, along) in trip.1.stop_times.iter().zip(along) {
                 if !graph.stop_names.contains_key(&stop_times.stop.id) {
                     graph.add_stop(stop_times.stop.id.clone(), stop_times.stop.name.clone(), stop_times.stop.latitude, stop_times.stop.longitude);
                 }
-                if last_stop.is_some() && last_arrival.is_some() && stop_times.arrival_time.is_some() {
-                    graph.add_edge(last_stop.clone().unwrap(), last_arrival.unwrap(), stop_times.stop.id.clone(), stop_times.arrival_time.unwrap());
+                untimed.push((stop_times.stop.id.clone(), stop_times.arrival_time, stop_times.departure_time, along));
+            }
+            let trip_stop_times = interpolate_times(&untimed);
+            for hop in trip_stop_times.windows(2) {
+                graph.add_edge(hop[0].0.clone(), hop[0].1, hop[1].0.clone(), hop[1].1);
+            }
+            // frequency based trips are a template run once per headway
+            let instances = if trip.1.frequencies.is_empty() {
+                vec![(trip.1.id.clone(), trip_stop_times)]
+            } else {
+                let frequencies: Vec<(u32, u32, u32)> = trip.1.frequencies.iter().map(|frequency| (frequency.start_time, frequency.end_time, frequency.headway_secs)).collect();
+                expand_frequencies(&trip.1.id, &trip_stop_times, &frequencies)
+            };
+            for (trip_id, stop_times) in instances {
+                for (stop_id, arrival, departure) in &stop_times {
+                    graph.add_stoptime(trip.1.route_id.clone(), stop_id.clone(), trip.1.service_id.clone(), *arrival, *departure, trip.1.direction_id.unwrap_or(Outbound), trip_id.clone());
+                }
+                graph.add_trip(trip_id.clone(), trip.1.route_id.clone(), trip.1.service_id.clone(), stop_times);
+                if let Some(headsign) = trip.1.trip_headsign.as_ref().filter(|_| graph.trips.contains_key(&trip_id)) {
+                    graph.headsigns.insert(trip_id, headsign.clone());
                 }
-                last_stop = Some(stop_times.stop.id.clone());
-                last_arrival = stop_times.arrival_time;
-                graph.add_stoptime(trip.1.route_id.clone(), stop_times.stop.id.clone(), trip.1.service_id.clone(), stop_times.arrival_time.unwrap(), trip.1.direction_id.unwrap_or_else(|| Outbound), trip.1.id.clone());
             }
         }
+        for (shape_id, stops) in shaped {
+            let located: Option<Vec<(String, f64, f64)>> = stops.into_iter()
+                .map(|id| gfts_rail.stops.get(&id).and_then(|stop| Some((id, stop.longitude?, stop.latitude?))))
+                .collect();
+            if let (Some(shape), Some(located)) = (gfts_rail.shapes.get(&shape_id), located) {
+                let shape: Vec<(f64, f64)> = shape.iter().map(|point| (point.longitude, point.latitude)).collect();
+                graph.add_shape(&shape, &located);
+            }
+        }
+        for stop in gfts_rail.stops.values() {
+            graph.add_location(stop.id.clone(), stop.name.clone(), LocationType::from_gtfs(stop.location_type), stop.parent_station.clone(), stop.latitude, stop.longitude);
+        }
         graph.clean();
         graph
     }
+
+    //several feeds in one graph, each namespaced by its onestop id, with walking transfers between
+    //stops of different feeds up to max_distance metres apart, all in one timezone. like from_file_between, route on
+    //a service_day of it
+    pub fn from_files(feeds: &[(String, String)], start: NaiveDate, end: NaiveDate, max_distance: f64) -> Result<Self, Box<dyn Error>> {
+        let mut graph: Option<GTFSGraph> = None;
+        for (file, onestop_id) in feeds {
+            let feed = GTFSGraph::from_file_between(file, onestop_id, start, end).namespaced();
+            match graph.as_mut() {
+                Some(graph) => graph.merge(feed)?,
+                None => graph = Some(feed),
+            }
+        }
+        let mut graph = graph.unwrap_or_else(|| GTFSGraph::new(""));
+        graph.link_feeds(max_distance);
+        Ok(graph)
+    }
+
+    //the timetable of one day: only the trips whose service runs on date, times still from that day's midnight
+    pub fn service_day(&self, date: NaiveDate) -> Self {
+        let mut day = self.clone();
+        day.trips.retain(|_, trip| self.calendar.runs(&trip.service_id, date));
+        day.headsigns.retain(|trip_id, _| day.trips.contains_key(trip_id));
+        for trips in day.stop_trips.values_mut() {
+            trips.retain(|(trip_id, _)| day.trips.contains_key(trip_id));
+        }
+        day
+    }
+
+    //prefix every route, stop, service and trip id with "onestop_id:" so feeds can be merged without collisions
+    pub fn namespaced(self) -> Self {
+        let prefix = |id: &String| format!("{}:{}", self.onestop_id, id);
+        let routes = self.routes.iter().map(|(route, stops)| {
+            let stops = stops.iter().map(|(stop, services)| {
+                let services = services.iter().map(|(service, times)| {
+                    let times = times.iter().map(|(arrival, departure, trip)| (*arrival, *departure, prefix(trip))).collect();
+                    (prefix(service), times)
+                }).collect();
+                (prefix(stop), services)
+            }).collect();
+            (prefix(route), stops)
+        }).collect();
+        let trips = self.trips.iter().map(|(id, trip)| {
+            let stop_times = trip.stop_times.iter().map(|(stop, arrival, departure)| (prefix(stop), *arrival, *departure)).collect();
+            (prefix(id), GTFSTrip { route_id: prefix(&trip.route_id), service_id: prefix(&trip.service_id), stop_times })
+        }).collect();
+        let mut calendar = Calendar::new(&self.calendar.timezone);
+        calendar.services = self.calendar.services.iter().map(|(id, pattern)| (prefix(id), pattern.clone())).collect();
+        calendar.exceptions = self.calendar.exceptions.iter().map(|(id, dates)| (prefix(id), dates.clone())).collect();
+        Self {
+            onestop_id: self.onestop_id.clone(),
+            old_services: self.old_services.iter().map(prefix).collect(),
+            route_names: self.route_names.iter().map(|(id, name)| (prefix(id), name.clone())).collect(),
+            stops: self.stops.iter().map(|stop| GTFSNode { id: prefix(&stop.id), lon: stop.lon, lat: stop.lat }).collect(),
+            stop_names: self.stop_names.iter().map(|(id, name)| (prefix(id), name.clone())).collect(),
+            edges: self.edges.iter().map(|((from, to), edge)| ((prefix(from), prefix(to)), edge.clone())).collect(),
+            routes,
+            trips,
+            calendar,
+            feeds: self.feeds.clone(),
+            transfers: self.transfers.iter().map(|((from, to), seconds)| ((prefix(from), prefix(to)), *seconds)).collect(),
+            station_rules: StationRules {
+                transfers: self.station_rules.transfers.iter().map(|rule| TransferRule {
+                    from_stop: rule.from_stop.as_ref().map(prefix),
+                    to_stop: rule.to_stop.as_ref().map(prefix),
+                    from_route: rule.from_route.as_ref().map(prefix),
+                    to_route: rule.to_route.as_ref().map(prefix),
+                    from_trip: rule.from_trip.as_ref().map(prefix),
+                    to_trip: rule.to_trip.as_ref().map(prefix),
+                    ..rule.clone()
+                }).collect(),
+                pathways: self.station_rules.pathways.iter().map(|pathway| Pathway {
+                    id: prefix(&pathway.id),
+                    from_stop: prefix(&pathway.from_stop),
+                    to_stop: prefix(&pathway.to_stop),
+                    ..pathway.clone()
+                }).collect(),
+                levels: self.station_rules.levels.iter().map(|(id, level)| (prefix(id), Level { id: prefix(id), ..level.clone() })).collect(),
+            },
+            locations: self.locations.iter().map(|(id, location)| (prefix(id), GTFSLocation { parent: location.parent.as_ref().map(prefix), ..location.clone() })).collect(),
+            children: self.children.iter().map(|(id, children)| (prefix(id), children.iter().map(prefix).collect())).collect(),
+            headsigns: self.headsigns.iter().map(|(id, headsign)| (prefix(id), headsign.clone())).collect(),
+            stop_trips: self.stop_trips.iter().map(|(stop, trips)| (prefix(stop), trips.iter().map(|(trip, position)| (prefix(trip), *position)).collect())).collect(),
+        }
+    }
+
+    //add another namespaced feed. times are seconds from the local midnight, so a feed in another timezone is refused
+    pub fn merge(&mut self, other: GTFSGraph) -> Result<(), Box<dyn Error>> {
+        if other.calendar.timezone != self.calendar.timezone {
+            return Err(format!("{} runs on {}, not {}", other.onestop_id, other.calendar.timezone, self.calendar.timezone).into());
+        }
+        self.old_services.extend(other.old_services);
+        self.route_names.extend(other.route_names);
+        self.stops.extend(other.stops);
+        self.stop_names.extend(other.stop_names);
+        self.edges.extend(other.edges);
+        self.routes.extend(other.routes);
+        self.trips.extend(other.trips);
+        self.calendar.services.extend(other.calendar.services);
+        self.calendar.exceptions.extend(other.calendar.exceptions);
+        self.feeds.extend(other.feeds);
+        self.transfers.extend(other.transfers);
+        self.headsigns.extend(other.headsigns);
+        self.station_rules.transfers.extend(other.station_rules.transfers);
+        self.station_rules.pathways.extend(other.station_rules.pathways);
+        self.station_rules.levels.extend(other.station_rules.levels);
+        self.locations.extend(other.locations);
+        self.children.extend(other.children);
+        for (stop, trips) in other.stop_trips {
+            self.stop_trips.entry(stop).or_default().extend(trips);
+        }
+        Ok(())
+    }
+
+    // a feed whose transfers, pathways or levels can't be read still routes, with the times it has
+    fn load_station_rules(&mut self, file: &str) {
+        match StationRules::from_file(file) {
+            Ok(rules) => self.add_station_rules(rules),
+            Err(error) => eprintln!("ignoring transfers, pathways and levels of {}: {}", file, error),
+        }
+    }
+
+    //walking times through station pathways, then the stop to stop rows of transfers.txt on top. rows naming a
+    //station stand for each of its platforms, a walk between stops without coordinates has no time and is left out
+    pub fn add_station_rules(&mut self, mut rules: StationRules) {
+        for (pair, seconds) in rules.pathway_times() {
+            let entry = self.transfers.entry(pair).or_insert(seconds);
+            *entry = (*entry).min(seconds);
+        }
+        rules.transfers = rules.transfers.iter().flat_map(|rule| self.for_platforms(rule)).collect();
+        let coords: HashMap<&str, &GTFSNode> = self.stops.iter().map(|stop| (stop.id.as_str(), stop)).collect();
+        // straight line walk when a rule links stops no pathway does
+        let walk = |from: &str, to: &str| match (coords.get(from), coords.get(to)) {
+            _ if from == to => Some(0),
+            (Some(from), Some(to)) => Some((from.distance(to, &()) / TRANSFER_WALK_SPEED).ceil() as u32),
+            _ => None,
+        };
+        let mut transfers = std::mem::take(&mut self.transfers);
+        for rule in rules.transfers.iter().filter(|rule| rule.is_stop_level()) {
+            let (from, to) = (rule.from_stop.clone().unwrap(), rule.to_stop.clone().unwrap());
+            let seconds = walk(&from, &to);
+            match rule.transfer_type {
+                // changing at the stop itself is ruled out by an endless change time
+                TransferType::NotPossible if from == to => { transfers.insert((from, to), u32::MAX); }
+                TransferType::NotPossible => { transfers.remove(&(from, to)); }
+                TransferType::MinimumTime => {
+                    if let Some(seconds) = rule.min_transfer_time.or(seconds) {
+                        transfers.insert((from, to), seconds);
+                    }
+                }
+                TransferType::Timed if from == to => { transfers.insert((from, to), 0); }
+                _ => {
+                    if let Some(seconds) = seconds {
+                        transfers.entry((from, to)).or_insert(seconds);
+                    }
+                }
+            }
+        }
+        self.transfers = transfers;
+        self.station_rules = rules;
+    }
+
+    // a transfers.txt row naming a station as one row for each of its platforms
+    fn for_platforms(&self, rule: &TransferRule) -> Vec<TransferRule> {
+        let expand = |stop: &Option<String>| -> Vec<Option<String>> {
+            let platforms = stop.as_deref().map(|stop| self.platforms(stop)).unwrap_or_default();
+            if platforms.is_empty() { vec![stop.clone()] } else { platforms.into_iter().map(|platform| Some(platform.to_string())).collect() }
+        };
+        let to_stops = expand(&rule.to_stop);
+        expand(&rule.from_stop).into_iter()
+            .flat_map(|from_stop| to_stops.iter().map(move |to_stop| TransferRule { from_stop: from_stop.clone(), to_stop: to_stop.clone(), ..rule.clone() }))
+            .collect()
+    }
+
+    //seconds needed between arriving on one trip and leaving on another, following transfers.txt
+    //down to trip pairs, None if the change is not possible
+    pub fn transfer_time(&self, from_stop: &str, to_stop: &str, from_trip: &str, to_trip: &str) -> Option<u32> {
+        let route = |trip: &str| self.trips.get(trip).map_or("", |trip| trip.route_id.as_str());
+        let walk = self.transfers.get(&(from_stop.to_string(), to_stop.to_string())).copied();
+        let walk = if from_stop == to_stop { Some(walk.unwrap_or(0)) } else { walk };
+        match self.station_rules.rule(from_stop, to_stop, (route(from_trip), from_trip), (route(to_trip), to_trip)) {
+            Some(rule) => match rule.transfer_type {
+                TransferType::NotPossible => None,
+                TransferType::Timed | TransferType::InSeat => Some(0),
+                TransferType::MinimumTime => rule.min_transfer_time.or(walk),
+                TransferType::Recommended | TransferType::ReBoard => walk,
+            },
+            None => walk.filter(|seconds| *seconds != u32::MAX),
+        }
+    }
+
+    //transfer_time of every change a route or trip level row of transfers.txt covers, keyed (from stop, to stop,
+    //from trip, to trip). changes at a stop or along a footpath from it, the rest follow transfers alone
+    pub fn ruled_transfers(&self) -> HashMap<(String, String, String, String), Option<u32>> {
+        let mut ruled = HashMap::new();
+        let rules: Vec<&TransferRule> = self.station_rules.transfers.iter().filter(|rule| rule.names_trips()).collect();
+        if rules.is_empty() {
+            return ruled;
+        }
+        // trips riders get off at each stop, and trips they get on
+        let mut alighting: HashMap<&str, Vec<&str>> = HashMap::new();
+        let mut boarding: HashMap<&str, Vec<&str>> = HashMap::new();
+        for (trip_id, trip) in &self.trips {
+            for (position, (stop, _, _)) in trip.stop_times.iter().enumerate() {
+                if position > 0 {
+                    alighting.entry(stop).or_default().push(trip_id);
+                }
+                if position + 1 < trip.stop_times.len() {
+                    boarding.entry(stop).or_default().push(trip_id);
+                }
+            }
+        }
+        let mut walks: HashMap<&str, Vec<&str>> = HashMap::new();
+        for (from, to) in self.transfers.keys().filter(|(from, to)| from != to) {
+            walks.entry(from).or_default().push(to);
+        }
+        let rides = |route: &Option<String>, trip: &Option<String>, trip_id: &str| {
+            trip.as_deref().is_none_or(|trip| trip == trip_id)
+                && route.as_deref().is_none_or(|route| self.trips.get(trip_id).is_some_and(|trip| trip.route_id == route))
+        };
+        for rule in rules {
+            let from_stops: Vec<&str> = match &rule.from_stop {
+                Some(stop) => vec![stop.as_str()],
+                None => alighting.keys().copied().collect(),
+            };
+            for from_stop in from_stops {
+                let to_stops = std::iter::once(from_stop).chain(walks.get(from_stop).into_iter().flatten().copied())
+                    .filter(|to_stop| rule.to_stop.as_deref().is_none_or(|stop| stop == *to_stop));
+                for to_stop in to_stops {
+                    for from_trip in alighting.get(from_stop).into_iter().flatten().filter(|trip| rides(&rule.from_route, &rule.from_trip, trip)) {
+                        for to_trip in boarding.get(to_stop).into_iter().flatten().filter(|trip| rides(&rule.to_route, &rule.to_trip, trip)) {
+                            let key = (from_stop.to_string(), to_stop.to_string(), from_trip.to_string(), to_trip.to_string());
+                            if from_trip != to_trip && !ruled.contains_key(&key) {
+                                ruled.insert(key, self.transfer_time(from_stop, to_stop, from_trip, to_trip));
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        ruled
+    }
+
+    //walking transfers both ways between stops of different feeds up to max_distance metres apart,
+    //returns how many were added
+    pub fn link_feeds(&mut self, max_distance: f64) -> usize {
+        if self.stops.is_empty() {
+            return 0;
+        }
+        let feed = |id: &str| id.split_once(':').map(|(feed, _)| feed.to_string());
+        let tree = vpsearch::Tree::new(&self.stops);
+        let mut added = 0;
+        for stop in &self.stops {
+            for index in tree.find_nearest_custom(stop, &(), RadiusBasedNeighborhood::new(max_distance)) {
+                let other = &self.stops[index];
+                if feed(&stop.id) == feed(&other.id) {
+                    continue;
+                }
+                let seconds = (stop.distance(other, &()) / TRANSFER_WALK_SPEED).ceil() as u32;
+                if self.transfers.insert((stop.id.clone(), other.id.clone()), seconds).is_none() {
+                    added += 1;
+                }
+            }
+        }
+        added
+    }
  
 }
+//seconds since midnight as HH:MM:SS, past 24:00:00 for trips running after midnight
+pub fn format_time(seconds: u32) -> String {
+    format!("{:02}:{:02}:{:02}", seconds / 3600, (seconds % 3600) / 60, seconds % 60)
+}
 
+//untimed stop times as timed ones. a stop with only one of the two times
+//waits no time there, untimed stops between two timed ones pass at a time in proportion to their distance along the
+//way, or evenly spaced when a distance is missing. untimed stops before the first or after the last timed one are dropped
+pub fn interpolate_times(stops: &UntimedStopTimes) -> StopTimes {
+    let timed: Vec<usize> = (0..stops.len()).filter(|&i| stops[i].1.is_some() || stops[i].2.is_some()).collect();
+    let mut stop_times = Vec::new();
+    for (n, &i) in timed.iter().enumerate() {
+        let (stop_id, arrival, departure, from) = &stops[i];
+        let arrival = arrival.or(*departure).unwrap();
+        let departure = departure.unwrap_or(arrival);
+        stop_times.push((stop_id.clone(), arrival, departure));
+        let Some(&j) = timed.get(n + 1) else {
+            break;
+        };
+        let (_, next_arrival, next_departure, to) = &stops[j];
+        let next_arrival = next_arrival.or(*next_departure).unwrap();
+        let span = next_arrival.saturating_sub(departure) as f64;
+        let distances: Option<Vec<f64>> = stops[i..=j].iter().map(|stop| stop.3).collect();
+        for k in i + 1..j {
+            let fraction = match (&distances, from, to) {
+                (Some(distances), Some(from), Some(to)) if to > from => (distances[k - i] - from) / (to - from),
+                _ => (k - i) as f64 / (j - i) as f64,
+            };
+            let passing = departure + (span * fraction.clamp(0.0, 1.0)).round() as u32;
+            stop_times.push((stops[k].0.clone(), passing, passing));
+        }
+    }
+    stop_times
+}
 
+//frequencies.txt (start_time, end_time, headway_secs) rows as concrete trips, exact_times style: one departure
+//from the first stop every headway from start_time until before end_time, keeping the template's running times.
+//instances are named trip_id@HH:MM:SS after their first departure
+pub fn expand_frequencies(trip_id: &str, stop_times: &[(String, u32, u32)], frequencies: &[(u32, u32, u32)]) -> Vec<(String, StopTimes)> {
+    let Some((_, _, first_departure)) = stop_times.first() else {
+        return Vec::new();
+    };
+    let mut instances = Vec::new();
+    for (start_time, end_time, headway) in frequencies {
+        if *headway == 0 {
+            continue;
+        }
+        for start in (*start_time..*end_time).step_by(*headway as usize) {
+            let shifted = stop_times.iter()
+                .map(|(stop_id, arrival, departure)| (stop_id.clone(), (arrival + start).saturating_sub(*first_departure), departure + start - first_departure))
+                .collect();
+            instances.push((format!("{}@{}", trip_id, format_time(start)), shifted));
+        }
+    }
+    instances
+}
+
+//each stop projected onto the shape, all (lon, lat), no earlier than the previous stop, and the shape cut between
+//consecutive ones. a stop goes to the earliest pass within SHAPE_MATCH_SLACK of its closest, so a loop starting and
+//ending at one stop is cut from its start
+pub fn shape_segments(shape: &[(f64, f64)], stops: &[(f64, f64)]) -> Vec<Vec<(f64, f64)>> {
+    if shape.len() < 2 {
+        return vec![Vec::new(); stops.len().saturating_sub(1)];
+    }
+    let lat_lon = |(lon, lat): (f64, f64)| (lat, lon);
+    // (segment, metres along it, point) where each stop meets the shape
+    let mut positions: Vec<(usize, f64, (f64, f64))> = Vec::new();
+    let mut cursor = (0, 0.0, shape[0]);
+    for stop in stops {
+        // cheap planar distances narrow the segments down before the geodesic projection
+        let planar: Vec<f64> = (cursor.0..shape.len() - 1).map(|segment| planar_distance(shape[segment], shape[segment + 1], *stop)).collect();
+        let closest = planar.iter().copied().fold(f64::INFINITY, f64::min);
+        let mut candidates: Vec<(usize, f64, (f64, f64), f64)> = Vec::new();
+        for (segment, planar) in (cursor.0..).zip(planar) {
+            if planar > closest + SHAPE_MATCH_SLACK {
+                continue;
+            }
+            let (intercept, along) = point_to_segment(lat_lon(shape[segment]), lat_lon(shape[segment + 1]), lat_lon(*stop));
+            // never behind the previous stop
+            if segment == cursor.0 && along < cursor.1 {
+                let geod = Geodesic::wgs84();
+                let distance: f64 = geod.inverse(cursor.2.1, cursor.2.0, stop.1, stop.0);
+                candidates.push((segment, cursor.1, cursor.2, distance));
+            } else {
+                candidates.push((segment, along, (intercept.lon, intercept.lat), intercept.dist));
+            }
+        }
+        let best = candidates.iter().map(|candidate| candidate.3).fold(f64::INFINITY, f64::min);
+        if let Some((segment, along, point, _)) = candidates.into_iter().find(|candidate| candidate.3 <= best + SHAPE_MATCH_SLACK) {
+            cursor = (segment, along, point);
+        }
+        positions.push(cursor);
+    }
+    positions.windows(2).map(|hop| {
+        let ((from, _, start), (to, _, end)) = (hop[0], hop[1]);
+        let mut geometry = vec![start];
+        geometry.extend(shape[from + 1..=to.max(from)].iter().copied());
+        geometry.push(end);
+        geometry.dedup();
+        geometry
+    }).collect()
+}
+
+// metres from p to the segment ab in a local equirectangular frame, all (lon, lat)
+fn planar_distance(a: (f64, f64), b: (f64, f64), p: (f64, f64)) -> f64 {
+    let scale = p.1.to_radians().cos();
+    let (bx, by) = ((b.0 - a.0) * scale, b.1 - a.1);
+    let (px, py) = ((p.0 - a.0) * scale, p.1 - a.1);
+    let squared = bx * bx + by * by;
+    let t = if squared > 0.0 { ((px * bx + py * by) / squared).clamp(0.0, 1.0) } else { 0.0 };
+    let (dx, dy) = (px - t * bx, py - t * by);
+    // metres per degree of latitude
+    (dx * dx + dy * dy).sqrt() * 111_320.0
+}
 
 #[derive(Debug, Clone)]
 pub struct Graph {
     pub nodes: Vec<Node>,
     pub edges: Vec<Edge>,
+    //dense index of every node and edge endpoint, in the order they were added
+    pub interner: NodeInterner,
 }
 
 #[derive(Debug, Clone, Copy, serde::Deserialize, PartialEq, PartialOrd)]
@@ -315,6 +999,7 @@ impl Graph {
         Self {
             nodes: Vec::new(),
             edges: Vec::new(),
+            interner: NodeInterner::new(),
         }
     }
 
@@ -322,6 +1007,7 @@ impl Graph {
         let mut graph = Self {
             nodes: Vec::new(),
             edges: Vec::new(),
+            interner: NodeInterner::new(),
         };
         /*let file = File::open(edge_file_path).unwrap();
         let mut rdr = ReaderBuilder::new().from_reader(file);
@@ -447,6 +1133,7 @@ impl Graph {
         let graph = Arc::new(Mutex::new(Self {
             nodes: Vec::new(),
             edges: Vec::new(),
+            interner: NodeInterner::new(),
         }));
         let edges = File::open(edge_file_path).unwrap();
         let records: Vec<StringRecord> = ReaderBuilder::new().from_reader(edges).records().collect::<Result<_, _>>().unwrap();
@@ -569,6 +1256,7 @@ impl Graph {
         let mut graph = Self {
             nodes: Vec::new(),
             edges: Vec::new(),
+            interner: NodeInterner::new(),
         };
         /*let file = File::open(edge_file_path).unwrap();
         let mut rdr = ReaderBuilder::new().from_reader(file);
@@ -643,17 +1331,24 @@ impl Graph {
     }
 
     pub fn add_node(&mut self, id: u64, lon: f64, lat: f64) {
-        self.nodes.push(Node::new(id, lon, lat));
+        self.add_node_obj(Node::new(id, lon, lat));
     }
 
     pub fn add_node_obj(&mut self, node: Node) {
+        self.interner.intern(node.id);
         self.nodes.push(node);
     }
     pub fn add_edge(&mut self, id: String, osm_id: String, source: String, target: String, length: f64, foot: bool, car_forward: String, car_backward: String, bike_forward: bool, bike_backward: bool, train: String, linestring: Vec<Node>) {
-        self.edges.push(Edge::new(id, osm_id, source, target, length, foot, car_forward, car_backward, bike_forward, bike_backward, train, linestring))
+        self.add_edge_obj(Edge::new(id, osm_id, source, target, length, foot, car_forward, car_backward, bike_forward, bike_backward, train, linestring))
     }
 
+    //endpoints stay strings on the edge, the ones that are osm node ids get their dense index here
     pub fn add_edge_obj(&mut self, edge: Edge) {
+        for endpoint in [&edge.source, &edge.target] {
+            if let Ok(id) = endpoint.parse::<u64>() {
+                self.interner.intern(id);
+            }
+        }
         self.edges.push(edge);
     }
 
@@ -690,3 +1385,256 @@ impl Graph {
     }
 
 }
+
+//street graphs for the routing tests
+#[cfg(test)]
+pub mod fixtures {
+    use super::{Edge, Graph, Node};
+
+    //straight edge from a to b, walkable and cyclable both ways and closed to cars and trains. tests change what
+    //they need with struct update syntax
+    pub fn path(id: &str, a: Node, b: Node, length: f64) -> Edge {
+        Edge::new(id.to_string(), id.to_string(), a.id.to_string(), b.id.to_string(), length, true, "Forbidden".to_string(), "Forbidden".to_string(), true, true, "Forbidden".to_string(), vec![a, b])
+    }
+
+    //the nodes joined by (id, index of a, index of b, metres) paths
+    pub fn paths(nodes: &[Node], paths: &[(&str, usize, usize, f64)]) -> Graph {
+        let mut graph = Graph::new();
+        for node in nodes {
+            graph.add_node_obj(*node);
+        }
+        for (id, a, b, length) in paths {
+            graph.add_edge_obj(path(id, nodes[*a], nodes[*b], *length));
+        }
+        graph
+    }
+
+    // 6x6 grid of 100 m blocks, every third street is a one way
+    pub fn grid() -> Graph {
+        let mut graph = Graph::new();
+        let id = |x: u64, y: u64| y * 6 + x + 1;
+        for y in 0..6 {
+            for x in 0..6 {
+                graph.add_node_obj(Node::new(id(x, y), -118.0 + x as f64 * 0.001, 34.0 + y as f64 * 0.0009));
+            }
+        }
+        for y in 0..6 {
+            for x in 0..6 {
+                for (dx, dy) in [(1, 0), (0, 1)] {
+                    if x + dx >= 6 || y + dy >= 6 {
+                        continue;
+                    }
+                    let (a, b) = (graph.nodes[id(x, y) as usize - 1], graph.nodes[id(x + dx, y + dy) as usize - 1]);
+                    let backward = if (x + y) % 3 == 0 { "Forbidden" } else { "Residential" };
+                    let class = if y == 2 { "Primary" } else { "Residential" };
+                    let edge_id = format!("{}-{}", a.id, b.id);
+                    graph.add_edge_obj(Edge { car_forward: class.to_string(), car_backward: backward.to_string(), ..path(&edge_id, a, b, 100.0 + (x * y) as f64) });
+                }
+            }
+        }
+        graph
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{expand_frequencies, format_time, interpolate_times, shape_segments, GTFSGraph, LocationType};
+    use crate::transfers::{StationRules, TransferRule, TransferType};
+    use approx::assert_relative_eq;
+    use chrono::{NaiveDate, NaiveDateTime};
+
+    fn date(y: i32, m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(y, m, d).unwrap()
+    }
+    use gtfs_structures::DirectionType::{Inbound, Outbound};
+
+    #[test]
+    fn test_timetable_in_seconds() {
+        let mut gtfs = GTFSGraph::new("test");
+        gtfs.add_route("red".to_string(), "Red Line".to_string());
+        // "9:00" would sort after "10:00" and "25:10" as strings
+        for (arrival, trip) in [(25 * 3600 + 600, "late"), (9 * 3600, "nine"), (10 * 3600 + 30, "ten")] {
+            gtfs.add_stoptime("red".to_string(), "A".to_string(), "weekday".to_string(), arrival, arrival + 45, Outbound, trip.to_string());
+        }
+        gtfs.add_stoptime("red".to_string(), "A".to_string(), "weekday".to_string(), 8 * 3600, 8 * 3600, Inbound, "back".to_string());
+        gtfs.clean();
+        let times = &gtfs.routes["red"]["A"]["weekday-0"];
+        let trips: Vec<&str> = times.iter().map(|(_, _, trip)| trip.as_str()).collect();
+        assert_eq!(trips, vec!["nine", "ten", "late"]);
+        assert_eq!(times[1], (10 * 3600 + 30, 10 * 3600 + 75, "ten".to_string()));
+        assert_eq!(gtfs.routes["red"]["A"]["weekday-1"].len(), 1);
+        assert_eq!(format_time(times[2].0), "25:10:00");
+    }
+
+    #[test]
+    fn test_interpolate_times() {
+        let stop = |id: &str, arrival: Option<u32>, departure: Option<u32>, along: Option<f64>| (id.to_string(), arrival, departure, along);
+        // X before the first timed stop and Z after the last have nothing to go by
+        let stops = vec![
+            stop("X", None, None, Some(0.0)),
+            stop("A", Some(3600), Some(3660), Some(100.0)),
+            stop("B", None, None, Some(400.0)),
+            stop("C", None, None, Some(1000.0)),
+            stop("D", None, Some(4260), Some(1100.0)),
+            stop("E", None, None, None),
+            stop("F", Some(4500), None, Some(1500.0)),
+            stop("Z", None, None, Some(2000.0)),
+        ];
+        let times = interpolate_times(&stops);
+        let ids: Vec<&str> = times.iter().map(|(id, _, _)| id.as_str()).collect();
+        assert_eq!(ids, vec!["A", "B", "C", "D", "E", "F"]);
+        assert_eq!(times[0], ("A".to_string(), 3600, 3660));
+        // 600 seconds over 1000 metres from A's departure
+        assert_eq!(times[1], ("B".to_string(), 3660 + 180, 3660 + 180));
+        assert_eq!(times[2], ("C".to_string(), 3660 + 540, 3660 + 540));
+        assert_eq!(times[3], ("D".to_string(), 4260, 4260));
+        // halfway by count without a distance
+        assert_eq!(times[4], ("E".to_string(), 4380, 4380));
+        assert_eq!(times[5], ("F".to_string(), 4500, 4500));
+        assert!(interpolate_times(&vec![stop("A", None, None, None)]).is_empty());
+    }
+
+    #[test]
+    fn test_expand_frequencies() {
+        let template = vec![("A".to_string(), 3600, 3630), ("B".to_string(), 4200, 4260), ("C".to_string(), 4800, 4800)];
+        // every 10 minutes from 6:00 until before 6:30, then every 20 from 6:30 to 7:00
+        let instances = expand_frequencies("loop", &template, &[(6 * 3600, 6 * 3600 + 1800, 600), (6 * 3600 + 1800, 7 * 3600, 1200)]);
+        let ids: Vec<&str> = instances.iter().map(|(id, _)| id.as_str()).collect();
+        assert_eq!(ids, vec!["loop@06:00:00", "loop@06:10:00", "loop@06:20:00", "loop@06:30:00", "loop@06:50:00"]);
+        let (_, first) = &instances[0];
+        assert_eq!(first[0], ("A".to_string(), 6 * 3600 - 30, 6 * 3600));
+        assert_eq!(first[2], ("C".to_string(), 6 * 3600 + 1170, 6 * 3600 + 1170));
+        assert_eq!(instances[4].1[1].2, 6 * 3600 + 3000 + 630);
+
+        let mut gtfs = GTFSGraph::new("test");
+        for (id, stop_times) in instances {
+            gtfs.add_trip(id, "loop".to_string(), "weekday".to_string(), stop_times);
+        }
+        assert_eq!(gtfs.trips.len(), 5);
+    }
+
+    #[test]
+    fn test_shape_segments() {
+        // a square loop north, east, south and back west to where it started
+        let shape = [(-118.0, 34.0), (-118.0, 34.005), (-118.0, 34.01), (-117.99, 34.01), (-117.99, 34.0), (-118.0, 34.0)];
+        let stops = [(-117.9999, 34.0), (-118.0001, 34.007), (-117.99, 34.005), (-118.0, 34.0)];
+        let hops = shape_segments(&shape, &stops);
+        assert_eq!(hops.len(), 3);
+        // the first stop is also next to the end of the loop, it takes the start
+        assert_relative_eq!(hops[0][0].1, 34.0, epsilon = 1e-6);
+        assert_relative_eq!(hops[0][0].0, -118.0, epsilon = 1e-6);
+        assert_eq!(hops[0][1], (-118.0, 34.005));
+        assert_relative_eq!(hops[0][2].1, 34.007, epsilon = 1e-6);
+        assert_eq!(&hops[1][1..3], &[(-118.0, 34.01), (-117.99, 34.01)]);
+        assert_relative_eq!(hops[1][3].1, 34.005, epsilon = 1e-6);
+        // and the last one the end
+        assert_eq!(hops[2][1], (-117.99, 34.0));
+        assert_relative_eq!(hops[2].last().unwrap().0, -118.0, epsilon = 1e-6);
+
+        let mut gtfs = GTFSGraph::new("test");
+        gtfs.add_edge("A".to_string(), 0, "B".to_string(), 120);
+        gtfs.add_edge("A".to_string(), 0, "B".to_string(), 150);
+        let located = [("A".to_string(), stops[0].0, stops[0].1), ("B".to_string(), stops[1].0, stops[1].1)];
+        gtfs.add_shape(&shape, &located);
+        assert_eq!(gtfs.edges[&("A".to_string(), "B".to_string())].weights.len(), 2);
+        assert_eq!(gtfs.edges[&("A".to_string(), "B".to_string())].geometry, hops[0]);
+    }
+
+    #[test]
+    fn test_station_hierarchy() {
+        let mut gtfs = GTFSGraph::new("test");
+        gtfs.add_location("union".to_string(), "Union Station".to_string(), LocationType::Station, None, Some(34.056), Some(-118.234));
+        gtfs.add_location("union-a".to_string(), "Union Station A".to_string(), LocationType::Platform, Some("union".to_string()), None, None);
+        gtfs.add_location("union-b".to_string(), "Union Station B".to_string(), LocationType::Platform, Some("union".to_string()), None, None);
+        gtfs.add_location("union-b-1".to_string(), "Car 1".to_string(), LocationType::BoardingArea, Some("union-b".to_string()), None, None);
+        gtfs.add_location("union-alameda".to_string(), "Alameda St".to_string(), LocationType::Entrance, Some("union".to_string()), None, None);
+        gtfs.add_location("7th".to_string(), "7th St".to_string(), LocationType::Platform, None, None, None);
+        gtfs.add_trip("red-1".to_string(), "red".to_string(), "weekday".to_string(), vec![("union-a".to_string(), 100, 120), ("7th".to_string(), 400, 400)]);
+        gtfs.add_trip("gold-1".to_string(), "gold".to_string(), "weekday".to_string(), vec![("7th".to_string(), 0, 0), ("union-b".to_string(), 60, 90), ("east".to_string(), 600, 600)]);
+        gtfs.add_trip("red-2".to_string(), "red".to_string(), "weekday".to_string(), vec![("7th".to_string(), 500, 500), ("union-a".to_string(), 800, 800)]);
+
+        assert_eq!(gtfs.platforms("union"), vec!["union-a", "union-b"]);
+        assert_eq!(gtfs.entrances("union"), vec!["union-alameda"]);
+        assert_eq!(gtfs.station("union-b-1"), "union");
+        assert_eq!(gtfs.station("7th"), "7th");
+        gtfs.add_location("loop-a".to_string(), "Loop A".to_string(), LocationType::Platform, Some("loop-b".to_string()), None, None);
+        gtfs.add_location("loop-b".to_string(), "Loop B".to_string(), LocationType::Platform, Some("loop-a".to_string()), None, None);
+        assert!(["loop-a", "loop-b"].contains(&gtfs.station("loop-a")));
+
+        // both platforms, but not red-2 which ends there
+        let departures = gtfs.departures("union-a");
+        let departures: Vec<(u32, &str, &str)> = departures.iter().map(|departure| (departure.departure, departure.trip_id.as_str(), departure.stop_id.as_str())).collect();
+        assert_eq!(departures, vec![(90, "gold-1", "union-b"), (120, "red-1", "union-a")]);
+        assert_eq!(gtfs.departures("union").len(), 2);
+        assert_eq!(gtfs.departures("union")[0].destination, "east");
+
+        // a rule for the station covers changing between its platforms, a walk to a stop without coordinates has no time
+        let rule = |from: &str, to: &str, transfer_type, min_transfer_time| TransferRule {
+            from_stop: Some(from.to_string()), to_stop: Some(to.to_string()), from_route: None, to_route: None, from_trip: None, to_trip: None, transfer_type, min_transfer_time,
+        };
+        gtfs.add_station_rules(StationRules { transfers: vec![rule("union", "union", TransferType::MinimumTime, Some(300)), rule("union-a", "7th", TransferType::Recommended, None)], ..Default::default() });
+        assert_eq!(gtfs.transfers.get(&("union-b".to_string(), "union-a".to_string())), Some(&300));
+        assert_eq!(gtfs.transfer_time("union-b", "union-a", "gold-1", "red-1"), Some(300));
+        assert!(!gtfs.transfers.contains_key(&("union-a".to_string(), "7th".to_string())));
+    }
+
+    #[test]
+    fn test_next_departures() {
+        let mut gtfs = GTFSGraph::new("test");
+        gtfs.add_route("red".to_string(), "Red Line".to_string());
+        gtfs.add_location("union".to_string(), "Union Station".to_string(), LocationType::Station, None, None, None);
+        gtfs.add_location("union-a".to_string(), "Union Station A".to_string(), LocationType::Platform, Some("union".to_string()), None, None);
+        gtfs.add_stop("7th".to_string(), "7th St".to_string(), None, None);
+        gtfs.calendar.add_service("weekday".to_string(), [true, true, true, true, true, false, false], date(2024, 1, 1), date(2024, 12, 31));
+        gtfs.add_trip("morning".to_string(), "red".to_string(), "weekday".to_string(), vec![("union-a".to_string(), 8 * 3600, 8 * 3600), ("7th".to_string(), 8 * 3600 + 600, 8 * 3600 + 600)]);
+        // leaves at half past midnight the day after its service day
+        gtfs.add_trip("owl".to_string(), "red".to_string(), "weekday".to_string(), vec![("union-a".to_string(), 24 * 3600 + 1800, 24 * 3600 + 1800), ("7th".to_string(), 25 * 3600, 25 * 3600)]);
+        gtfs.headsigns.insert("owl".to_string(), "Owl to 7th".to_string());
+
+        let at = |d: u32, h: u32, m: u32| date(2024, 1, d).and_hms_opt(h, m, 0).unwrap();
+        // tuesday morning: the morning trip, then tuesday's owl early on wednesday
+        let board = gtfs.next_departures("union", at(16, 7, 0), 3);
+        let rows: Vec<(&str, NaiveDateTime)> = board.iter().map(|row| (row.trip_id.as_str(), row.time)).collect();
+        assert_eq!(rows, vec![("morning", at(16, 8, 0)), ("owl", at(17, 0, 30)), ("morning", at(17, 8, 0))]);
+        assert_eq!(board[0].headsign, "7th St");
+        assert_eq!(board[0].route_name, "Red Line");
+        assert_eq!(board[1].headsign, "Owl to 7th");
+        assert_eq!(board[1].service_date, date(2024, 1, 16));
+        assert_eq!(board[1].stop_id, "union-a");
+
+        // early saturday only friday's owl is left, nothing runs on the weekend
+        let board = gtfs.next_departures("union-a", at(20, 0, 10), 5);
+        assert_eq!(board.len(), 1);
+        assert_eq!((board[0].time, board[0].service_date), (at(20, 0, 30), date(2024, 1, 19)));
+        assert!(gtfs.next_departures("union-a", at(20, 0, 31), 5).is_empty());
+    }
+
+    #[test]
+    fn test_merge_and_link_feeds() {
+        // a rail station and a bus stop about 80 m apart, the bus feed reusing the stop id "1"
+        let mut rail = GTFSGraph::new("rail");
+        rail.add_stop("1".to_string(), "Union Station".to_string(), Some(34.0560), Some(-118.2340));
+        rail.add_stop("2".to_string(), "7th St".to_string(), Some(34.0487), Some(-118.2585));
+        rail.add_trip("t".to_string(), "red".to_string(), "weekday".to_string(), vec![("1".to_string(), 100, 100), ("2".to_string(), 400, 400)]);
+        let mut bus = GTFSGraph::new("bus");
+        bus.add_stop("1".to_string(), "Union Station Bus".to_string(), Some(34.0567), Some(-118.2340));
+        bus.add_trip("t".to_string(), "33".to_string(), "weekday".to_string(), vec![("1".to_string(), 900, 900), ("9".to_string(), 1500, 1500)]);
+
+        let mut graph = rail.namespaced();
+        let mut elsewhere = GTFSGraph::new("elsewhere");
+        elsewhere.calendar.timezone = "America/New_York".to_string();
+        assert!(graph.merge(elsewhere.namespaced()).is_err());
+        graph.merge(bus.namespaced()).unwrap();
+        assert_eq!(graph.feeds, vec!["rail", "bus"]);
+        assert_eq!(graph.feed_of("bus:t"), ("bus", "t"));
+        assert_eq!(graph.feed_of("1"), ("rail", "1"));
+        assert_eq!(graph.trips.len(), 2);
+        assert_eq!(graph.trips["bus:t"].stop_times[0].0, "bus:1");
+        assert_eq!(graph.stop_names["rail:1"], "Union Station");
+
+        assert_eq!(graph.link_feeds(150.0), 2);
+        let seconds = graph.transfers[&("rail:1".to_string(), "bus:1".to_string())];
+        assert!((50..70).contains(&seconds));
+        assert!(!graph.transfers.contains_key(&("rail:1".to_string(), "rail:2".to_string())));
+    }
+}
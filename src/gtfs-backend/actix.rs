@@ -1,5 +1,19 @@
 
 use actix_web::middleware::DefaultHeaders;
+#[path = "../graph.rs"]
+mod graph;
+#[path = "../calendar.rs"]
+mod calendar;
+#[path = "../transfers.rs"]
+mod transfers;
+#[path = "../geodesic.rs"]
+mod geodesic;
+#[path = "../interner.rs"]
+mod interner;
+#[path = "../routing.rs"]
+mod routing;
+#[path = "../snap.rs"]
+mod snap;
 
 mod models {
     use serde::{Deserialize, Serialize};
@@ -28,6 +42,8 @@ mod errors {
     #[derive(Display, From, Debug)]
     pub enum MyError {
         NotFound,
+        #[from(ignore)]
+        BadRequest(String),
         PGError(PGError),
         PGMError(PGMError),
         PoolError(PoolError),
@@ -38,6 +54,7 @@ mod errors {
         fn error_response(&self) -> HttpResponse {
             match *self {
                 MyError::NotFound => HttpResponse::NotFound().finish(),
+                MyError::BadRequest(ref message) => HttpResponse::BadRequest().body(message.clone()),
                 MyError::PoolError(ref err) => {
                     HttpResponse::InternalServerError().body(err.to_string())
                 }
@@ -67,11 +84,67 @@ mod db {
     }
 }
 
+mod streets {
+    use std::collections::HashMap;
+    use serde_json::{json, Value};
+    use crate::graph::Graph;
+    use crate::routing::{Profile, Router};
+    use crate::snap::{EdgeSnapper, Snap};
+
+    // metres a requested point may be from the nearest usable street
+    pub const MAX_SNAP: f64 = 500.0;
+
+    //street graph kept in memory for the lifetime of the server
+    pub struct Streets {
+        pub graph: Graph,
+        pub routers: HashMap<Profile, Router>,
+        pub snapper: EdgeSnapper,
+    }
+
+    impl Streets {
+        pub fn load(edges: &str, nodes: &str) -> Self {
+            let graph = Graph::from_csv(edges, nodes);
+            let routers = [Profile::Foot, Profile::Bike, Profile::Car].into_iter()
+                .map(|profile| (profile, Router::new(&graph, profile)))
+                .collect();
+            let snapper = EdgeSnapper::new(&graph);
+            Self { graph, routers, snapper }
+        }
+
+        //nearest point on an edge the profile may use in at least one direction
+        pub fn nearest(&self, profile: Profile, lat: f64, lon: f64) -> Option<Snap> {
+            self.snapper.snap(&self.graph, lon, lat, MAX_SNAP, |edge| profile.speed(edge, true).is_some() || profile.speed(edge, false).is_some())
+        }
+
+        pub fn route(&self, profile: Profile, from: (f64, f64), to: (f64, f64)) -> Option<Value> {
+            let source = self.nearest(profile, from.0, from.1)?;
+            let target = self.nearest(profile, to.0, to.1)?;
+            let route = source.route_to(&target, &self.graph, &self.routers[&profile])?;
+            let coordinates: Vec<[f64; 2]> = route.geometry.iter().map(|node| [node.lon, node.lat]).collect();
+            Some(json!({
+                "type": "Feature",
+                "properties": { "profile": profile, "distance": route.distance, "duration": route.duration },
+                "geometry": { "type": "LineString", "coordinates": coordinates },
+            }))
+        }
+
+        pub fn nearest_feature(&self, profile: Profile, lat: f64, lon: f64) -> Option<Value> {
+            let snap = self.nearest(profile, lat, lon)?;
+            let edge = &self.graph.edges[snap.edge];
+            Some(json!({
+                "type": "Feature",
+                "properties": { "profile": profile, "edge": edge.id, "osm_id": edge.osm_id, "distance": snap.distance, "offset": snap.offset },
+                "geometry": { "type": "Point", "coordinates": [snap.lon, snap.lat] },
+            }))
+        }
+    }
+}
+
 mod handlers {
     use actix_web::{web, Error, HttpResponse, HttpRequest};
     use deadpool_postgres::{Client, Pool};
     use qstring::QString;
-    use crate::{db, errors::MyError};
+    use crate::{db, errors::MyError, routing::Profile, streets::Streets};
 
     pub async fn index(db_pool: web::Data<Pool>, req: HttpRequest) -> Result<HttpResponse, Error> {
         let qs = QString::from(req.query_string());
@@ -105,12 +178,46 @@ mod handlers {
         //println!("{:#?}", timetable.clone());
         Ok(HttpResponse::Ok().json(timetable))
     }
+
+    //"lat,lon"
+    fn lat_lon(qs: &QString, key: &str) -> Result<(f64, f64), MyError> {
+        let value = qs.get(key).ok_or_else(|| MyError::BadRequest(format!("missing {}", key)))?;
+        match value.split_once(',').map(|(lat, lon)| (lat.trim().parse::<f64>(), lon.trim().parse::<f64>())) {
+            Some((Ok(lat), Ok(lon))) => Ok((lat, lon)),
+            _ => Err(MyError::BadRequest(format!("{} should be lat,lon", key))),
+        }
+    }
+
+    fn profile(qs: &QString) -> Result<Profile, MyError> {
+        match qs.get("profile") {
+            Some(profile) => profile.parse().map_err(MyError::BadRequest),
+            None => Ok(Profile::Foot),
+        }
+    }
+
+    pub async fn route(streets: web::Data<Streets>, req: HttpRequest) -> Result<HttpResponse, Error> {
+        let qs = QString::from(req.query_string());
+        let from = lat_lon(&qs, "from")?;
+        let to = lat_lon(&qs, "to")?;
+        let profile = profile(&qs)?;
+        let route = web::block(move || streets.route(profile, from, to)).await?;
+        Ok(HttpResponse::Ok().json(route.ok_or(MyError::NotFound)?))
+    }
+
+    pub async fn nearest(streets: web::Data<Streets>, req: HttpRequest) -> Result<HttpResponse, Error> {
+        let qs = QString::from(req.query_string());
+        let (lat, lon) = lat_lon(&qs, "point")?;
+        let profile = profile(&qs)?;
+        let nearest = streets.nearest_feature(profile, lat, lon).ok_or(MyError::NotFound)?;
+        Ok(HttpResponse::Ok().json(nearest))
+    }
 }
 
 use actix_web::{web, App, HttpServer};
 use deadpool_postgres::{Manager, ManagerConfig, Pool, RecyclingMethod};
 use tokio_postgres::NoTls;
-use handlers::index;
+use handlers::{index, nearest, route};
+use streets::Streets;
 
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
@@ -126,6 +233,12 @@ async fn main() -> std::io::Result<()> {
     let mgr = Manager::from_config(pg_config, NoTls, mgr_config);
     let pool = Pool::builder(mgr).max_size(16).build().unwrap();
 
+    let args = arguments::parse(std::env::args()).unwrap();
+    let edges = args.get::<String>("edges").unwrap_or_else(|| "edges.csv".to_string());
+    let nodes = args.get::<String>("nodes").unwrap_or_else(|| "nodes.csv".to_string());
+    let streets = web::Data::new(Streets::load(&edges, &nodes));
+    println!("Loaded {} street edges", streets.graph.edges.len());
+
     let server = HttpServer::new(move || {
         App::new()
         .wrap(actix_block_ai_crawling::BlockAi)
@@ -136,10 +249,14 @@ async fn main() -> std::io::Result<()> {
             "*",
         ))
         )
-        .app_data(web::Data::new(pool.clone())).service(
+        .app_data(web::Data::new(pool.clone()))
+        .app_data(streets.clone())
+        .service(
             web::resource("/")
                 .route(web::get().to(index)),
         )
+        .service(web::resource("/route").route(web::get().to(route)))
+        .service(web::resource("/nearest").route(web::get().to(nearest)))
     })
     .bind("127.0.0.1:8080")?
     .run();
@@ -1,5 +1,9 @@
 use std::time::Instant;
 mod graph;
+mod calendar;
+mod transfers;
+mod geodesic;
+mod interner;
 use graph::GTFSGraph;
 fn main() {
     let start_time = Instant::now();
@@ -0,0 +1,80 @@
+use std::collections::HashMap;
+use serde::{Serialize, Deserialize};
+
+//osm node id <-> dense index, so adjacency can live in vectors instead of maps keyed by 64-bit ids
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct NodeInterner {
+    //dense index -> osm node id
+    ids: Vec<u64>,
+    index: HashMap<u64, usize>,
+}
+
+impl NodeInterner {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    //index of id, assigning the next free one the first time it is seen
+    pub fn intern(&mut self, id: u64) -> usize {
+        if let Some(index) = self.index.get(&id) {
+            return *index;
+        }
+        let index = self.ids.len();
+        self.ids.push(id);
+        self.index.insert(id, index);
+        index
+    }
+
+    pub fn get(&self, id: u64) -> Option<usize> {
+        self.index.get(&id).copied()
+    }
+
+    //parses string endpoints as stored on Graph edges
+    pub fn get_str(&self, id: &str) -> Option<usize> {
+        self.get(id.parse().ok()?)
+    }
+
+    pub fn id(&self, index: usize) -> u64 {
+        self.ids[index]
+    }
+
+    pub fn len(&self) -> usize {
+        self.ids.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ids.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::graph::fixtures::path;
+    use crate::graph::{Edge, Graph, Node};
+
+    #[test]
+    fn test_round_trip() {
+        let mut graph = Graph::new();
+        let (a, b) = (Node::new(1833121478, -118.0, 34.0), Node::new(1597291791, -118.001, 34.0));
+        graph.add_node_obj(a);
+        // the target is only known from the edge
+        graph.add_edge_obj(Edge { osm_id: "1".to_string(), ..path("e", a, b, 92.0) });
+        let mut interner = graph.interner.clone();
+        assert_eq!(interner.len(), 2);
+        assert_eq!(interner.get(1833121478), Some(0));
+        assert_eq!(interner.get_str("1597291791"), Some(1));
+        assert_eq!(interner.id(1), 1597291791);
+        assert_eq!(interner.intern(1597291791), 1);
+        assert_eq!(interner.intern(42), 2);
+        assert_eq!(interner.get(7), None);
+    }
+
+    #[test]
+    fn test_loaded_graph() {
+        let graph = Graph::from_csv("testedges.csv", "testnodes.csv");
+        let interner = &graph.interner;
+        assert!(graph.nodes.iter().all(|node| interner.get(node.id).is_some()));
+        assert!(graph.edges.iter().all(|edge| interner.get_str(&edge.source).is_some() && interner.get_str(&edge.target).is_some()));
+        assert!((0..interner.len()).all(|index| interner.get(interner.id(index)) == Some(index)));
+    }
+}
@@ -0,0 +1,162 @@
+use std::collections::HashMap;
+use geo::{ConcaveHull, LineString, MultiPoint, Point, Polygon};
+use serde_json::{json, Value};
+use crate::graph::{Graph, Node};
+use crate::routing::Router;
+use crate::snap::{cut_linestring, linestring_length, EdgeSnapper};
+
+// concaveman concavity, lower hugs the reached streets more tightly
+const CONCAVITY: f64 = 2.0;
+
+#[derive(Debug, Clone)]
+pub struct ReachableEdge {
+    //index into Graph::edges
+    pub edge: usize,
+    //reachable (start, end) metres along the linestring, partially reached edges are cut
+    pub ranges: Vec<(f64, f64)>,
+    pub geometry: Vec<Vec<Node>>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Isochrone {
+    pub seconds: f64,
+    pub edges: Vec<ReachableEdge>,
+    pub polygon: Polygon<f64>,
+}
+
+//everywhere reachable from (lon, lat) within each of the bands, given in seconds
+pub fn isochrones(graph: &Graph, router: &Router, snapper: &EdgeSnapper, lon: f64, lat: f64, max_snap: f64, bands: &[f64]) -> Option<Vec<Isochrone>> {
+    let profile = router.profile;
+    let snap = snapper.snap(graph, lon, lat, max_snap, |edge| profile.speed(edge, true).is_some() || profile.speed(edge, false).is_some())?;
+    let limit = bands.iter().copied().fold(0.0, f64::max);
+    let search = router.dijkstra(&snap.departures(graph, router), None, limit, |arc| arc.duration);
+
+    let mut lengths: HashMap<usize, f64> = HashMap::new();
+    let mut length_of = |edge: usize| *lengths.entry(edge).or_insert_with(|| linestring_length(&graph.edges[edge].linestring));
+
+    let mut result = Vec::new();
+    for seconds in bands {
+        let mut ranges: HashMap<usize, Vec<(f64, f64)>> = HashMap::new();
+        for (node, cost) in search.cost.iter().enumerate() {
+            if *cost > *seconds {
+                continue;
+            }
+            for arc in &router.arcs[node] {
+                let length = length_of(arc.edge);
+                let fraction = if arc.duration > 0.0 { ((seconds - cost) / arc.duration).min(1.0) } else { 1.0 };
+                let range = if arc.forward { (0.0, fraction * length) } else { ((1.0 - fraction) * length, length) };
+                ranges.entry(arc.edge).or_default().push(range);
+            }
+        }
+
+        // the snapped edge is walked straight from the snap point
+        let edge = &graph.edges[snap.edge];
+        let length = length_of(snap.edge);
+        // linestring metres per metre of the edge length the durations are based on
+        let scale = if edge.length > 0.0 { length / edge.length } else { 1.0 };
+        if let Some(speed) = profile.speed(edge, true) {
+            ranges.entry(snap.edge).or_default().push((snap.offset, (snap.offset + seconds * speed * scale).min(length)));
+        }
+        if let Some(speed) = profile.speed(edge, false) {
+            ranges.entry(snap.edge).or_default().push(((snap.offset - seconds * speed * scale).max(0.0), snap.offset));
+        }
+
+        let mut edges: Vec<ReachableEdge> = ranges.into_iter().map(|(edge, ranges)| {
+            let ranges = merge_ranges(ranges);
+            let geometry = ranges.iter().map(|(start, end)| cut_linestring(&graph.edges[edge].linestring, *start, *end)).collect();
+            ReachableEdge { edge, ranges, geometry }
+        }).collect();
+        edges.sort_by_key(|reachable| reachable.edge);
+
+        let points: MultiPoint<f64> = edges.iter()
+            .flat_map(|reachable| reachable.geometry.iter().flatten())
+            .map(|node| Point::new(node.lon, node.lat))
+            .collect();
+        let polygon = if points.0.len() >= 3 {
+            points.concave_hull(CONCAVITY)
+        } else {
+            Polygon::new(LineString::new(vec![]), vec![])
+        };
+        result.push(Isochrone { seconds: *seconds, edges, polygon });
+    }
+    Some(result)
+}
+
+fn merge_ranges(mut ranges: Vec<(f64, f64)>) -> Vec<(f64, f64)> {
+    ranges.retain(|(start, end)| end > start);
+    ranges.sort_by(|a, b| a.0.total_cmp(&b.0));
+    let mut merged: Vec<(f64, f64)> = Vec::new();
+    for (start, end) in ranges {
+        match merged.last_mut() {
+            Some(last) if start <= last.1 => last.1 = last.1.max(end),
+            _ => merged.push((start, end)),
+        }
+    }
+    merged
+}
+
+//one polygon and one multilinestring feature per band
+pub fn to_geojson(isochrones: &[Isochrone]) -> Value {
+    let mut features = Vec::new();
+    for isochrone in isochrones {
+        let ring: Vec<[f64; 2]> = isochrone.polygon.exterior().coords().map(|c| [c.x, c.y]).collect();
+        features.push(json!({
+            "type": "Feature",
+            "properties": { "seconds": isochrone.seconds, "kind": "polygon" },
+            "geometry": { "type": "Polygon", "coordinates": [ring] },
+        }));
+        let lines: Vec<Vec<[f64; 2]>> = isochrone.edges.iter()
+            .flat_map(|reachable| reachable.geometry.iter())
+            .map(|line| line.iter().map(|node| [node.lon, node.lat]).collect())
+            .collect();
+        features.push(json!({
+            "type": "Feature",
+            "properties": { "seconds": isochrone.seconds, "kind": "edges" },
+            "geometry": { "type": "MultiLineString", "coordinates": lines },
+        }));
+    }
+    json!({ "type": "FeatureCollection", "features": features })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::isochrones;
+    use crate::graph::fixtures::paths;
+    use crate::graph::{Graph, Node};
+    use crate::routing::{Profile, Router};
+    use crate::snap::{linestring_length, EdgeSnapper};
+    use approx::assert_relative_eq;
+
+    #[test]
+    fn test_partial_edge_is_cut() {
+        // two 1 km edges heading north
+        let nodes = [Node::new(1, -118.0, 34.0), Node::new(2, -118.0, 34.009), Node::new(3, -118.0, 34.018)];
+        let graph = paths(&nodes, &[("a", 0, 1, linestring_length(&nodes[0..2])), ("b", 1, 2, linestring_length(&nodes[1..3]))]);
+        let router = Router::new(&graph, Profile::Foot);
+        let snapper = EdgeSnapper::new(&graph);
+        let bands = isochrones(&graph, &router, &snapper, -118.0, 34.0, 50.0, &[500.0, 1000.0]).unwrap();
+
+        assert_eq!(bands[0].edges.len(), 1);
+        assert_relative_eq!(bands[0].edges[0].ranges[0].1, 700.0, epsilon = 1e-6);
+        assert_eq!(bands[1].edges.len(), 2);
+        let second = &bands[1].edges[1];
+        assert_eq!(second.edge, 1);
+        assert_relative_eq!(second.ranges[0].0, 0.0);
+        assert_relative_eq!(second.ranges[0].1, 1400.0 - graph.edges[0].length, epsilon = 1e-3);
+        let cut = second.geometry[0].last().unwrap();
+        assert!(cut.lat > 34.009 && cut.lat < 34.018);
+    }
+
+    #[test]
+    fn test_bands_grow() {
+        let graph = Graph::from_csv("testedges.csv", "testnodes.csv");
+        let router = Router::new(&graph, Profile::Foot);
+        let snapper = EdgeSnapper::new(&graph);
+        let bands = isochrones(&graph, &router, &snapper, -119.0343115, 33.4837658, 50.0, &[300.0, 600.0, 1200.0]).unwrap();
+        let reached = |band: usize| -> f64 { bands[band].edges.iter().flat_map(|edge| edge.ranges.iter()).map(|(start, end)| end - start).sum() };
+        assert!(reached(0) > 0.0);
+        assert!(reached(0) < reached(1));
+        assert!(reached(1) < reached(2));
+        assert!(bands[2].polygon.exterior().0.len() >= 4);
+    }
+}
@@ -0,0 +1,38 @@
+use std::{fs, time::Instant};
+mod graph;
+mod calendar;
+mod transfers;
+mod geodesic;
+mod interner;
+mod routing;
+mod snap;
+mod isochrone;
+use graph::Graph;
+use routing::{Profile, Router};
+use snap::EdgeSnapper;
+
+fn main() {
+    let args = arguments::parse(std::env::args()).unwrap();
+    let edges = args.get::<String>("edges").unwrap_or_else(|| "testedges.csv".to_string());
+    let nodes = args.get::<String>("nodes").unwrap_or_else(|| "testnodes.csv".to_string());
+    let profile = args.get::<Profile>("profile").unwrap_or(Profile::Foot);
+    let lon = args.get::<f64>("lon").unwrap_or(-119.0343115);
+    let lat = args.get::<f64>("lat").unwrap_or(33.4837658);
+    let minutes = args.get_all::<f64>("minutes").unwrap_or_else(|| vec![10.0, 20.0, 30.0]);
+
+    let start_time = Instant::now();
+    let graph = Graph::from_csv(&edges, &nodes);
+    let router = Router::new(&graph, profile);
+    let snapper = EdgeSnapper::new(&graph);
+    eprintln!("loading took {:?}", start_time.elapsed().as_secs_f64());
+
+    let start_time = Instant::now();
+    let bands: Vec<f64> = minutes.iter().map(|minutes| minutes * 60.0).collect();
+    match isochrone::isochrones(&graph, &router, &snapper, lon, lat, 500.0, &bands) {
+        Some(isochrones) => {
+            eprintln!("isochrones took {:?}", start_time.elapsed().as_secs_f64());
+            fs::write("isochrones.geojson", isochrone::to_geojson(&isochrones).to_string()).unwrap();
+        }
+        None => println!("No street near {}, {}", lat, lon),
+    }
+}
@@ -0,0 +1,58 @@
+use std::time::Instant;
+use chrono::NaiveDate;
+mod graph;
+mod calendar;
+mod transfers;
+mod geodesic;
+mod interner;
+mod raptor;
+use graph::{format_time, GTFSGraph};
+use raptor::{Leg, Raptor};
+
+fn main() {
+    let args = arguments::parse(std::env::args()).unwrap();
+    let feed = args.get::<String>("feed").unwrap_or_else(|| "gtfs_rail.zip".to_string());
+    let origin = args.get::<String>("from").unwrap_or_else(|| "80101".to_string());
+    let destination = args.get::<String>("to").unwrap_or_else(|| "80211".to_string());
+    let departure = args.get::<u32>("departure").unwrap_or(8 * 3600);
+    let max_transfers = args.get::<usize>("transfers").unwrap_or(3);
+
+    let start_time = Instant::now();
+    //YYYY-MM-DD service day, today in the agency's timezone if not given
+    let gtfs = match args.get::<String>("date").and_then(|date| NaiveDate::parse_from_str(&date, "%Y-%m-%d").ok()) {
+        Some(date) => GTFSGraph::from_file_between(&feed, "f-9q5-metro~losangeles~rail", date, date),
+        None => GTFSGraph::from_file(&feed, "f-9q5-metro~losangeles~rail"),
+    };
+    eprintln!("from_file took {:?}", start_time.elapsed().as_secs_f64());
+    let start_time = Instant::now();
+    let raptor = Raptor::new(&gtfs);
+    eprintln!("Raptor::new took {:?}, {} patterns", start_time.elapsed().as_secs_f64(), raptor.patterns.len());
+
+    //seconds since midnight, every good option leaving from departure until then instead of only the earliest arrival
+    let until = args.get::<u32>("until");
+
+    let start_time = Instant::now();
+    let journeys = match until {
+        Some(until) => raptor.range(&origin, &destination, departure, until, max_transfers),
+        None => raptor.earliest_arrival(&origin, &destination, departure, max_transfers).into_iter().collect(),
+    };
+    eprintln!("query took {:?}", start_time.elapsed().as_secs_f64());
+    if journeys.is_empty() {
+        println!("No journey found");
+        return;
+    }
+    for journey in journeys {
+        println!("depart {} arrive {}, {} transfers", format_time(journey.departure), format_time(journey.arrival), journey.transfers);
+        for leg in journey.legs {
+            match leg {
+                Leg::Transit { route_id, trip_id, board_stop, board_time, alight_stop, alight_time } => {
+                    let name = gtfs.route_names.get(&route_id).cloned().unwrap_or(route_id);
+                    println!("  {} {} -> {} {}: {} trip {}", format_time(board_time), gtfs.stop_names.get(&board_stop).unwrap_or(&board_stop), gtfs.stop_names.get(&alight_stop).unwrap_or(&alight_stop), format_time(alight_time), name, trip_id);
+                }
+                Leg::Transfer { from_stop, to_stop, departure, arrival } => {
+                    println!("  {} walk {} -> {} {}", format_time(departure), from_stop, to_stop, format_time(arrival));
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,50 @@
+use std::time::Instant;
+mod graph;
+mod calendar;
+mod transfers;
+mod geodesic;
+mod interner;
+mod routing;
+mod astar;
+mod alt;
+use graph::Graph;
+use routing::{Profile, Router};
+use alt::{LandmarkMetric, LandmarkSelection, Landmarks};
+
+fn main() {
+    let args = arguments::parse(std::env::args()).unwrap();
+    let edges = args.get::<String>("edges").unwrap_or_else(|| "testedges.csv".to_string());
+    let nodes = args.get::<String>("nodes").unwrap_or_else(|| "testnodes.csv".to_string());
+    let source = args.get::<u64>("from").unwrap_or(2729443585);
+    let target = args.get::<u64>("to").unwrap_or(2729463686);
+    let count = args.get::<usize>("landmarks").unwrap_or(16);
+    let selection = match args.get::<String>("selection").as_deref() {
+        Some("farthest") => LandmarkSelection::Farthest,
+        _ => LandmarkSelection::Avoid,
+    };
+
+    let start_time = Instant::now();
+    let graph = Graph::from_csv(&edges, &nodes);
+    eprintln!("from_csv took {:?}", start_time.elapsed().as_secs_f64());
+
+    for (profile, name) in [(Profile::Foot, "foot"), (Profile::Bike, "bike"), (Profile::Car, "car")] {
+        let router = Router::new(&graph, profile);
+        let start_time = Instant::now();
+        let landmarks = Landmarks::new(&router, count, selection, LandmarkMetric::Duration);
+        eprintln!("{} landmarks took {:?}", name, start_time.elapsed().as_secs_f64());
+        landmarks.save(&format!("landmarks_{}.json", name)).unwrap();
+
+        let (Some(source), Some(target)) = (router.node_index(source), router.node_index(target)) else {
+            println!("{}: Unknown node", name);
+            continue;
+        };
+        let (_, dijkstra_stats) = router.shortest_path_with_stats(source, target);
+        let (_, astar_stats) = astar::astar(&router, source, target);
+        let (route, alt_stats) = landmarks.query(&router, source, target, 1.0, |arc| arc.duration);
+        println!("{}: dijkstra settled {}, astar settled {}, alt settled {} in {:?}ns", name, dijkstra_stats.settled, astar_stats.settled, alt_stats.settled, alt_stats.elapsed.as_nanos());
+        match route {
+            Some(route) => println!("{}: distance: {:.1} m, duration: {:.1} s", name, route.distance, route.duration),
+            None => println!("{}: No path found", name),
+        }
+    }
+}
@@ -1,4 +1,8 @@
 mod graph;
+mod calendar;
+mod transfers;
+mod geodesic;
+mod interner;
 use graph::Node;
 use graph::Edge;
 use std::time::Instant;
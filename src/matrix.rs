@@ -0,0 +1,134 @@
+use std::{error::Error, fs::File};
+use csv::{ReaderBuilder, Writer};
+use rayon::prelude::*;
+use serde::{Serialize, Deserialize};
+use serde_json::{json, Value};
+use crate::graph::Graph;
+use crate::routing::Router;
+use crate::snap::{EdgeSnapper, Snap};
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Location {
+    pub id: String,
+    pub lon: f64,
+    pub lat: f64,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+struct MatrixRow<'a> {
+    source: &'a str,
+    target: &'a str,
+    //empty when unreachable
+    duration: Option<f64>,
+    reachable: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct Matrix {
+    pub sources: Vec<Location>,
+    pub targets: Vec<Location>,
+    //seconds, durations[source][target], None when unreachable or not snapped
+    pub durations: Vec<Vec<Option<f64>>>,
+}
+
+pub fn read_locations(file_path: &str) -> Result<Vec<Location>, Box<dyn Error>> {
+    let file = File::open(file_path)?;
+    let mut rdr = ReaderBuilder::new().from_reader(file);
+    let mut locations = Vec::new();
+    for result in rdr.deserialize::<Location>() {
+        locations.push(result?);
+    }
+    Ok(locations)
+}
+
+impl Matrix {
+    //one bounded one-to-many search per source, run in parallel
+    pub fn new(graph: &Graph, router: &Router, snapper: &EdgeSnapper, sources: &[Location], targets: &[Location], max_snap: f64) -> Self {
+        let profile = router.profile;
+        let snap = |location: &Location| -> Option<Snap> {
+            snapper.snap(graph, location.lon, location.lat, max_snap, |edge| profile.speed(edge, true).is_some() || profile.speed(edge, false).is_some())
+        };
+        let source_snaps: Vec<Option<Snap>> = sources.par_iter().map(snap).collect();
+        let target_snaps: Vec<Option<Snap>> = targets.par_iter().map(snap).collect();
+        let arrivals: Vec<Vec<(usize, f64)>> = target_snaps.iter()
+            .map(|snap| snap.map(|snap| snap.arrivals(graph, router)).unwrap_or_default())
+            .collect();
+        let target_nodes: Vec<usize> = arrivals.iter().flatten().map(|(node, _)| *node).collect();
+
+        let durations = source_snaps.par_iter().map(|source| {
+            let Some(source) = source else {
+                return vec![None; targets.len()];
+            };
+            let search = router.one_to_many(&source.departures(graph, router), &target_nodes, f64::INFINITY);
+            target_snaps.iter().zip(&arrivals).map(|(target, arrivals)| {
+                let target = target.as_ref()?;
+                let through_nodes = arrivals.iter()
+                    .map(|(node, cost)| search.cost[*node] + cost)
+                    .fold(f64::INFINITY, f64::min);
+                let best = source.direct(target, graph, router).map_or(through_nodes, |direct| direct.min(through_nodes));
+                if best.is_finite() { Some(best) } else { None }
+            }).collect()
+        }).collect();
+
+        Self {
+            sources: sources.to_vec(),
+            targets: targets.to_vec(),
+            durations,
+        }
+    }
+
+    //one row per pair, unreachable pairs have an empty duration
+    pub fn to_csv(&self, file_path: &str) -> Result<(), Box<dyn Error>> {
+        let mut wtr = Writer::from_path(file_path)?;
+        for (source, row) in self.sources.iter().zip(&self.durations) {
+            for (target, duration) in self.targets.iter().zip(row) {
+                wtr.serialize(MatrixRow { source: &source.id, target: &target.id, duration: *duration, reachable: duration.is_some() })?;
+            }
+        }
+        wtr.flush()?;
+        Ok(())
+    }
+
+    //unreachable pairs are null
+    pub fn to_json(&self) -> Value {
+        json!({
+            "sources": self.sources.iter().map(|location| &location.id).collect::<Vec<_>>(),
+            "targets": self.targets.iter().map(|location| &location.id).collect::<Vec<_>>(),
+            "durations": self.durations,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Location, Matrix};
+    use crate::graph::Graph;
+    use crate::routing::{Profile, Router};
+    use crate::snap::EdgeSnapper;
+    use approx::assert_relative_eq;
+
+    fn location(id: &str, lon: f64, lat: f64) -> Location {
+        Location { id: id.to_string(), lon, lat }
+    }
+
+    #[test]
+    fn test_matrix_on_test_edges() {
+        let graph = Graph::from_csv("testedges.csv", "testnodes.csv");
+        let router = Router::new(&graph, Profile::Foot);
+        let snapper = EdgeSnapper::new(&graph);
+        let points = vec![
+            location("a", -119.0380069, 33.4669861),
+            location("b", -119.0303436, 33.4875751),
+            location("nowhere", -118.25, 34.05),
+        ];
+        let matrix = Matrix::new(&graph, &router, &snapper, &points, &points, 50.0);
+
+        assert_relative_eq!(matrix.durations[0][0].unwrap(), 0.0, epsilon = 1e-6);
+        let route = router.route(2729443585, 2729463686).unwrap();
+        assert_relative_eq!(matrix.durations[0][1].unwrap(), route.duration, epsilon = 1e-3);
+        assert_relative_eq!(matrix.durations[1][0].unwrap(), route.duration, epsilon = 1e-3);
+        assert!(matrix.durations[0][2].is_none());
+        assert!(matrix.durations[2].iter().all(|duration| duration.is_none()));
+        assert!(matrix.to_json()["durations"][2][0].is_null());
+    }
+}
@@ -0,0 +1,328 @@
+use std::collections::HashMap;
+use geographiclib_rs::{Geodesic, InverseGeodesic};
+use serde::Serialize;
+use crate::graph::{Graph, GTFSGraph, GTFSNode, RadiusBasedNeighborhood};
+use crate::raptor::{Journey, Leg, Raptor};
+use crate::routing::{Profile, Router};
+use crate::snap::{EdgeSnapper, Snap};
+
+// metres a stop may be from the nearest foot edge, as in stop_access
+const STOP_SNAP_DISTANCE: f64 = 200.0;
+
+//one part of a door to door itinerary
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub enum Step {
+    Walk {
+        //stop id, "origin" or "destination"
+        from: String,
+        to: String,
+        departure: u32,
+        arrival: u32,
+        //metres
+        distance: f64,
+        //(lon, lat) from the street linestrings, starting and ending at the points themselves
+        geometry: Vec<(f64, f64)>,
+    },
+    Transit {
+        route_id: String,
+        trip_id: String,
+        board_stop: String,
+        board_time: u32,
+        alight_stop: String,
+        alight_time: u32,
+    },
+}
+
+impl Step {
+    pub fn departure(&self) -> u32 {
+        match self {
+            Step::Walk { departure, .. } => *departure,
+            Step::Transit { board_time, .. } => *board_time,
+        }
+    }
+
+    pub fn arrival(&self) -> u32 {
+        match self {
+            Step::Walk { arrival, .. } => *arrival,
+            Step::Transit { alight_time, .. } => *alight_time,
+        }
+    }
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct Itinerary {
+    //seconds since midnight of the service day
+    pub departure: u32,
+    pub arrival: u32,
+    //seconds door to door
+    pub duration: u32,
+    //metres on foot
+    pub walk_distance: f64,
+    pub transfers: usize,
+    pub steps: Vec<Step>,
+}
+
+//a point and where it meets the walking network
+#[derive(Debug, Clone)]
+struct Place {
+    id: String,
+    lon: f64,
+    lat: f64,
+    snap: Snap,
+}
+
+//door to door trips walking on the street graph and riding the timetable
+pub struct Planner<'a> {
+    graph: &'a Graph,
+    router: Router,
+    snapper: EdgeSnapper,
+    pub raptor: Raptor,
+    //<stop id, stop snapped onto a foot edge>
+    stops: HashMap<String, Place>,
+    //the snapped stops, by straight line distance
+    stop_nodes: Vec<GTFSNode>,
+    stop_tree: vpsearch::Tree<GTFSNode>,
+    //(lon, lat) of every stop, snapped or not
+    locations: HashMap<String, (f64, f64)>,
+    //metres, for each walk on its own
+    pub max_walk: f64,
+    //metres per second
+    pub walk_speed: f64,
+}
+
+impl<'a> Planner<'a> {
+    //timetable footpaths are joined by street walks of up to max_walk metres between stops, gtfs is one service day
+    //as for Raptor::new
+    pub fn new(graph: &'a Graph, gtfs: &GTFSGraph, max_walk: f64, walk_speed: f64) -> Self {
+        let snapper = EdgeSnapper::new(graph);
+        let mut stops = HashMap::new();
+        let mut locations = HashMap::new();
+        for stop in &gtfs.stops {
+            locations.insert(stop.id.clone(), (stop.lon, stop.lat));
+            if let Some(snap) = snapper.snap(graph, stop.lon, stop.lat, STOP_SNAP_DISTANCE, |edge| edge.foot) {
+                stops.insert(stop.id.clone(), Place { id: stop.id.clone(), lon: stop.lon, lat: stop.lat, snap });
+            }
+        }
+        let mut stop_nodes: Vec<GTFSNode> = stops.values().map(|stop: &Place| GTFSNode { id: stop.id.clone(), lon: stop.lon, lat: stop.lat }).collect();
+        stop_nodes.sort_by(|a, b| a.id.cmp(&b.id));
+        let stop_tree = vpsearch::Tree::new(&stop_nodes);
+        let mut planner = Self {
+            graph,
+            router: Router::new(graph, Profile::Foot),
+            snapper,
+            raptor: Raptor::new(gtfs),
+            stops,
+            stop_nodes,
+            stop_tree,
+            locations,
+            max_walk,
+            walk_speed,
+        };
+
+        let mut from_stops: Vec<&String> = planner.stops.keys().collect();
+        from_stops.sort();
+        let mut footpaths = Vec::new();
+        for from in from_stops {
+            for (to, metres) in planner.walks(&planner.stops[from]) {
+                // transfers.txt and pathways know the station better than the street graph does
+                if to != *from && !gtfs.transfers.contains_key(&(from.clone(), to.clone())) {
+                    footpaths.push((from.clone(), to, planner.seconds(metres)));
+                }
+            }
+        }
+        for (from, to, seconds) in footpaths {
+            planner.raptor.add_transfer(&from, &to, seconds);
+        }
+        planner
+    }
+
+    fn seconds(&self, metres: f64) -> u32 {
+        (metres / self.walk_speed).ceil() as u32
+    }
+
+    //(stop id, metres) of the stops within max_walk of a snapped place, sorted by stop id. foot edges are walkable
+    //both ways, so these are also the metres from each stop to the place
+    fn walks(&self, place: &Place) -> Vec<(String, f64)> {
+        // no walk is shorter than the straight line, so only stops that close are worth a look, the metre over the
+        // limit covers the neighbourhood leaving out stops right at it
+        let point = GTFSNode { id: place.id.clone(), lon: place.lon, lat: place.lat };
+        let nearby = self.stop_tree.find_nearest_custom(&point, &(), RadiusBasedNeighborhood::new(self.max_walk + 1.0));
+        if nearby.is_empty() {
+            return Vec::new();
+        }
+        let from = &place.snap;
+        // walking runs at one speed everywhere, so seconds to the ends of an edge convert back to metres
+        let speed = Profile::Foot.max_speed();
+        let sources: Vec<(usize, f64)> = from.departures(self.graph, &self.router).into_iter()
+            .map(|(node, seconds)| (node, from.distance + seconds * speed))
+            .collect();
+        let search = self.router.dijkstra(&sources, None, self.max_walk, |arc| arc.length);
+        let mut walks: Vec<(String, f64)> = nearby.into_iter().map(|index| &self.stops[&self.stop_nodes[index].id]).filter_map(|stop| {
+            let through = stop.snap.arrivals(self.graph, &self.router).into_iter()
+                .map(|(node, seconds)| search.cost[node] + seconds * speed);
+            let direct = from.direct(&stop.snap, self.graph, &self.router).map(|seconds| from.distance + seconds * speed);
+            let metres = through.chain(direct).fold(f64::INFINITY, f64::min) + stop.snap.distance;
+            if metres <= self.max_walk { Some((stop.id.clone(), metres)) } else { None }
+        }).collect();
+        walks.sort_by(|a, b| a.0.cmp(&b.0));
+        walks
+    }
+
+    //street walk between two places, None if the network doesn't join them
+    fn walk(&self, from: &Place, to: &Place, departure: u32) -> Option<Step> {
+        let route = from.snap.route_to(&to.snap, self.graph, &self.router)?;
+        let mut geometry = vec![(from.lon, from.lat)];
+        geometry.extend(route.geometry.iter().map(|node| (node.lon, node.lat)));
+        geometry.push((to.lon, to.lat));
+        geometry.dedup();
+        let distance = from.snap.distance + route.distance + to.snap.distance;
+        Some(Step::Walk { from: from.id.clone(), to: to.id.clone(), departure, arrival: departure + self.seconds(distance), distance, geometry })
+    }
+
+    //walk between stops, straight across when one of them is off the street graph as inside a station
+    fn footpath(&self, from: &str, to: &str, departure: u32, arrival: u32) -> Step {
+        let walked = match (self.stops.get(from), self.stops.get(to)) {
+            (Some(from), Some(to)) => self.walk(from, to, departure),
+            _ => None,
+        };
+        let (distance, geometry) = match walked {
+            Some(Step::Walk { distance, geometry, .. }) => (distance, geometry),
+            _ => {
+                let (a, b) = (self.locations.get(from).copied().unwrap_or_default(), self.locations.get(to).copied().unwrap_or_default());
+                let metres: f64 = Geodesic::wgs84().inverse(a.1, a.0, b.1, b.0);
+                (metres, vec![a, b])
+            }
+        };
+        Step::Walk { from: from.to_string(), to: to.to_string(), departure, arrival, distance, geometry }
+    }
+
+    //the earliest arriving trip from one point to the other leaving after departure, and walking all the way when
+    //that is within max_walk, earliest arrival first
+    pub fn plan(&self, from: (f64, f64), to: (f64, f64), departure: u32, max_transfers: usize) -> Vec<Itinerary> {
+        let place = |id: &str, (lon, lat): (f64, f64)| -> Option<Place> {
+            let snap = self.snapper.snap(self.graph, lon, lat, self.max_walk, |edge| edge.foot)?;
+            Some(Place { id: id.to_string(), lon, lat, snap })
+        };
+        let (Some(origin), Some(destination)) = (place("origin", from), place("destination", to)) else {
+            return Vec::new();
+        };
+        let access: Vec<(String, u32)> = self.walks(&origin).into_iter().map(|(stop, metres)| (stop, self.seconds(metres))).collect();
+        let egress: Vec<(String, u32)> = self.walks(&destination).into_iter().map(|(stop, metres)| (stop, self.seconds(metres))).collect();
+
+        let mut itineraries = Vec::new();
+        if let Some(journey) = self.raptor.earliest_arrival_between(&access, &egress, departure, max_transfers) {
+            itineraries.extend(self.itinerary(&origin, &destination, journey));
+        }
+        if let Some(walk) = self.walk(&origin, &destination, departure) {
+            if let Step::Walk { distance, .. } = walk {
+                if distance <= self.max_walk {
+                    itineraries.push(Itinerary::new(vec![walk], 0));
+                }
+            }
+        }
+        itineraries.sort_by_key(|itinerary| (itinerary.arrival, itinerary.transfers));
+        itineraries
+    }
+
+    fn itinerary(&self, origin: &Place, destination: &Place, journey: Journey) -> Option<Itinerary> {
+        let mut steps: Vec<Step> = journey.legs.into_iter().map(|leg| match leg {
+            Leg::Transit { route_id, trip_id, board_stop, board_time, alight_stop, alight_time } =>
+                Step::Transit { route_id, trip_id, board_stop, board_time, alight_stop, alight_time },
+            Leg::Transfer { from_stop, to_stop, departure, arrival } => self.footpath(&from_stop, &to_stop, departure, arrival),
+        }).collect();
+        let first_stop = match steps.first()? {
+            Step::Walk { from, .. } => from.clone(),
+            Step::Transit { board_stop, .. } => board_stop.clone(),
+        };
+        let last_stop = match steps.last()? {
+            Step::Walk { to, .. } => to.clone(),
+            Step::Transit { alight_stop, .. } => alight_stop.clone(),
+        };
+        let access = self.walk(origin, self.stops.get(&first_stop)?, journey.departure)?;
+        let egress = self.walk(self.stops.get(&last_stop)?, destination, steps.last()?.arrival())?;
+        steps.insert(0, access);
+        steps.push(egress);
+
+        // leave just in time for the first vehicle, with every walk before it
+        if let Some(first) = steps.iter().position(|step| matches!(step, Step::Transit { .. })) {
+            let mut time = steps[first].departure();
+            for step in steps[..first].iter_mut().rev() {
+                if let Step::Walk { departure, arrival, .. } = step {
+                    let seconds = *arrival - *departure;
+                    *arrival = time;
+                    *departure = time - seconds;
+                    time = *departure;
+                }
+            }
+        }
+        Some(Itinerary::new(steps, journey.transfers))
+    }
+}
+
+impl Itinerary {
+    fn new(steps: Vec<Step>, transfers: usize) -> Self {
+        let departure = steps.first().map(Step::departure).unwrap_or_default();
+        let arrival = steps.last().map(Step::arrival).unwrap_or_default();
+        let walk_distance = steps.iter().map(|step| if let Step::Walk { distance, .. } = step { *distance } else { 0.0 }).sum();
+        Self { departure, arrival, duration: arrival - departure, walk_distance, transfers, steps }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Planner, Step};
+    use crate::graph::fixtures::path;
+    use crate::graph::{Graph, GTFSGraph, Node};
+    use crate::raptor::fixtures::{hms, trip};
+    use crate::snap::linestring_length;
+
+    // a 3 km street heading north with a stop near each end and a train between them
+    fn network() -> (Graph, GTFSGraph) {
+        let mut graph = Graph::new();
+        let nodes: Vec<Node> = (0..4).map(|i| Node::new(i + 1, -118.0, 34.0 + 0.009 * i as f64)).collect();
+        for node in &nodes {
+            graph.add_node_obj(*node);
+        }
+        for (a, b) in nodes.iter().zip(&nodes[1..]) {
+            graph.add_edge_obj(path(&format!("{}-{}", a.id, b.id), *a, *b, linestring_length(&[*a, *b])));
+        }
+        let mut gtfs = GTFSGraph::new("test");
+        gtfs.add_stop("south".to_string(), "South".to_string(), Some(34.002), Some(-117.9995));
+        gtfs.add_stop("north".to_string(), "North".to_string(), Some(34.025), Some(-117.9995));
+        for (id, start) in [("t1", hms(8, 0)), ("t2", hms(8, 20))] {
+            gtfs.add_trip(id.to_string(), "train".to_string(), "weekday".to_string(), trip(&[("south", start), ("north", start + 300)]));
+        }
+        (graph, gtfs)
+    }
+
+    #[test]
+    fn test_walk_ride_walk() {
+        let (graph, gtfs) = network();
+        let planner = Planner::new(&graph, &gtfs, 500.0, 1.4);
+        let itineraries = planner.plan((-118.0, 34.0), (-118.0, 34.027), hms(7, 55), 2);
+        // 3 km is too far to walk the whole way
+        assert_eq!(itineraries.len(), 1);
+        let itinerary = &itineraries[0];
+        assert_eq!(itinerary.steps.len(), 3);
+        assert!(matches!(&itinerary.steps[1], Step::Transit { trip_id, .. } if trip_id == "t1"));
+        let Step::Walk { departure, arrival, distance, geometry, .. } = &itinerary.steps[0] else { panic!("starts on foot") };
+        // about 220 m north along the street then 46 m across to the stop, leaving just in time for the train
+        assert!(*distance > 250.0 && *distance < 290.0);
+        assert_eq!(*arrival, hms(8, 0));
+        assert_eq!(*departure, hms(8, 0) - (distance / 1.4).ceil() as u32);
+        assert_eq!(geometry[0], (-118.0, 34.0));
+        assert_eq!(*geometry.last().unwrap(), (-117.9995, 34.002));
+        assert_eq!(itinerary.arrival, itinerary.steps[2].arrival());
+        assert_eq!(itinerary.duration, itinerary.arrival - itinerary.departure);
+
+        // a slower walker misses the first train
+        let slow = Planner::new(&graph, &gtfs, 500.0, 0.5);
+        let itinerary = &slow.plan((-118.0, 34.0), (-118.0, 34.027), hms(7, 55), 2)[0];
+        assert!(matches!(&itinerary.steps[1], Step::Transit { trip_id, .. } if trip_id == "t2"));
+
+        // walking only, close enough
+        let short = planner.plan((-118.0, 34.0), (-118.0, 34.002), hms(7, 55), 2);
+        assert_eq!(short[0].steps.len(), 1);
+        assert_eq!(short[0].transfers, 0);
+    }
+}
@@ -0,0 +1,585 @@
+use std::collections::HashMap;
+use serde::Serialize;
+use crate::graph::{GTFSGraph, GTFSTrip};
+
+// arrival time of stops not reached yet
+const UNREACHED: u32 = u32::MAX;
+
+//trips sharing a route and an exact stop sequence, none overtaking another, earliest first
+#[derive(Debug, Clone)]
+pub struct Pattern {
+    pub route_id: String,
+    //dense stop indices
+    pub stops: Vec<usize>,
+    pub trip_ids: Vec<String>,
+    //times[trip][position] = (arrival, departure), seconds since midnight
+    pub times: Vec<Vec<(u32, u32)>>,
+}
+
+impl Pattern {
+    //first trip leaving position at or after time
+    fn earliest_trip(&self, position: usize, time: u32) -> Option<usize> {
+        let trip = self.times.partition_point(|times| times[position].1 < time);
+        if trip < self.times.len() { Some(trip) } else { None }
+    }
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub enum Leg {
+    Transit {
+        route_id: String,
+        trip_id: String,
+        board_stop: String,
+        board_time: u32,
+        alight_stop: String,
+        alight_time: u32,
+    },
+    Transfer {
+        from_stop: String,
+        to_stop: String,
+        departure: u32,
+        arrival: u32,
+    },
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct Journey {
+    pub departure: u32,
+    pub arrival: u32,
+    pub transfers: usize,
+    pub legs: Vec<Leg>,
+}
+
+//a trip as (pattern, index in it)
+type Trip = (usize, usize);
+
+//how a stop was reached in a round
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Label {
+    Transit { pattern: usize, trip: usize, board: usize, alight: usize },
+    Transfer { from: usize, seconds: u32 },
+}
+
+#[derive(Debug, Clone)]
+pub struct Raptor {
+    pub stops: Vec<String>,
+    pub stop_index: HashMap<String, usize>,
+    pub patterns: Vec<Pattern>,
+    //(pattern, position in its stop sequence) serving each stop
+    pub stop_patterns: Vec<Vec<(usize, usize)>>,
+    //(to stop, seconds) footpaths from each stop
+    pub transfers: Vec<Vec<(usize, u32)>>,
+    //minimum seconds between alighting one vehicle and boarding another at each stop
+    pub change: Vec<u32>,
+    //seconds needed for the changes transfers.txt rules by route or trip, None when ruled out, keyed (from stop,
+    //to stop, from trip, to trip)
+    ruled: HashMap<(usize, usize, Trip, Trip), Option<u32>>,
+    //stops where a ruled change boards
+    ruled_boarding: Vec<bool>,
+}
+
+impl Raptor {
+    //every trip of gtfs runs in one day, so a graph loaded for several should be cut to a service_day first
+    pub fn new(gtfs: &GTFSGraph) -> Self {
+        let mut raptor = Self {
+            stops: Vec::new(),
+            stop_index: HashMap::new(),
+            patterns: Vec::new(),
+            stop_patterns: Vec::new(),
+            transfers: Vec::new(),
+            change: Vec::new(),
+            ruled: HashMap::new(),
+            ruled_boarding: Vec::new(),
+        };
+        let mut trips: Vec<(&String, &GTFSTrip)> = gtfs.trips.iter().collect();
+        trips.sort_by_key(|(id, trip)| (trip.stop_times[0].2, *id));
+
+        let mut by_sequence: HashMap<(String, Vec<usize>), Vec<usize>> = HashMap::new();
+        for (trip_id, trip) in trips {
+            let stops: Vec<usize> = trip.stop_times.iter().map(|(stop, _, _)| raptor.add_stop(stop)).collect();
+            let times: Vec<(u32, u32)> = trip.stop_times.iter().map(|(_, arrival, departure)| (*arrival, *departure)).collect();
+            let candidates = by_sequence.entry((trip.route_id.clone(), stops.clone())).or_default();
+            // trips arrive sorted by first departure, so a trip fits a pattern if it never passes the last one added
+            let fits = candidates.iter().copied().find(|pattern| {
+                let last = raptor.patterns[*pattern].times.last().unwrap();
+                last.iter().zip(&times).all(|(before, after)| before.0 <= after.0 && before.1 <= after.1)
+            });
+            let pattern = match fits {
+                Some(pattern) => pattern,
+                None => {
+                    raptor.patterns.push(Pattern { route_id: trip.route_id.clone(), stops: stops.clone(), trip_ids: Vec::new(), times: Vec::new() });
+                    candidates.push(raptor.patterns.len() - 1);
+                    raptor.patterns.len() - 1
+                }
+            };
+            raptor.patterns[pattern].trip_ids.push(trip_id.clone());
+            raptor.patterns[pattern].times.push(times);
+        }
+
+        for (index, pattern) in raptor.patterns.iter().enumerate() {
+            for (position, stop) in pattern.stops.iter().enumerate() {
+                raptor.stop_patterns[*stop].push((index, position));
+            }
+        }
+        let mut transfers: Vec<(&(String, String), &u32)> = gtfs.transfers.iter().collect();
+        transfers.sort();
+        for ((from, to), seconds) in transfers {
+            raptor.add_transfer(from, to, *seconds);
+        }
+
+        let trip_index: HashMap<&str, Trip> = raptor.patterns.iter().enumerate()
+            .flat_map(|(pattern, trips)| trips.trip_ids.iter().enumerate().map(move |(trip, id)| (id.as_str(), (pattern, trip))))
+            .collect();
+        raptor.ruled_boarding = vec![false; raptor.stops.len()];
+        for ((from_stop, to_stop, from_trip, to_trip), seconds) in gtfs.ruled_transfers() {
+            let (Some(from_stop), Some(to_stop), Some(from_trip), Some(to_trip)) =
+                (raptor.stop_index.get(&from_stop), raptor.stop_index.get(&to_stop), trip_index.get(from_trip.as_str()), trip_index.get(to_trip.as_str())) else {
+                continue;
+            };
+            raptor.ruled.insert((*from_stop, *to_stop, *from_trip, *to_trip), seconds);
+            raptor.ruled_boarding[*to_stop] = true;
+        }
+        raptor
+    }
+
+    fn add_stop(&mut self, id: &str) -> usize {
+        if let Some(index) = self.stop_index.get(id) {
+            return *index;
+        }
+        let index = self.stops.len();
+        self.stops.push(id.to_string());
+        self.stop_index.insert(id.to_string(), index);
+        self.stop_patterns.push(Vec::new());
+        self.transfers.push(Vec::new());
+        self.change.push(0);
+        index
+    }
+
+    //one way footpath between two stops served by the timetable, from a stop to itself it is the change time there
+    pub fn add_transfer(&mut self, from: &str, to: &str, seconds: u32) {
+        if let (Some(from), Some(to)) = (self.stop_index.get(from), self.stop_index.get(to)) {
+            if from == to {
+                self.change[*from] = seconds;
+            } else {
+                self.transfers[*from].push((*to, seconds));
+            }
+        }
+    }
+
+    //earliest arrival at destination leaving origin no earlier than departure, using at most max_transfers transfers
+    pub fn earliest_arrival(&self, origin: &str, destination: &str, departure: u32, max_transfers: usize) -> Option<Journey> {
+        let (origin, destination) = (*self.stop_index.get(origin)?, *self.stop_index.get(destination)?);
+        let n = self.stops.len();
+        let rounds = max_transfers + 1;
+        // arrival[k][stop] with at most k trips, labels[k][stop] set when round k improved it
+        let mut arrival: Vec<Vec<u32>> = vec![vec![UNREACHED; n]; rounds + 1];
+        let mut labels: Vec<Vec<Option<Label>>> = vec![vec![None; n]; rounds + 1];
+        let mut best = vec![UNREACHED; n];
+        let mut marked = vec![false; n];
+
+        arrival[0][origin] = departure;
+        best[origin] = departure;
+        marked[origin] = true;
+        self.relax_transfers(0, &mut arrival, &mut labels, &mut best, &mut marked, destination);
+
+        for k in 1..=rounds {
+            arrival[k] = arrival[k - 1].clone();
+            self.scan_round(k, &mut arrival, &mut labels, &mut best, &mut marked, destination);
+            if !marked.iter().any(|marked| *marked) {
+                break;
+            }
+        }
+
+        // fewest trips among the rounds reaching the earliest arrival
+        let round = (0..=rounds).filter(|k| arrival[*k][destination] != UNREACHED).min_by_key(|k| (arrival[*k][destination], *k))?;
+        Some(self.journey(departure, round, destination, &arrival, &labels))
+    }
+
+    //earliest arrival at a point off the network, reached by walking egress seconds from one of its stops, leaving a
+    //point from which the access stops are the given seconds away at departure. the journey ends at the egress stop used
+    //and always has a leg, walking straight from the point to the other one is up to the caller
+    pub fn earliest_arrival_between(&self, access: &[(String, u32)], egress: &[(String, u32)], departure: u32, max_transfers: usize) -> Option<Journey> {
+        let dense = |stops: &[(String, u32)]| -> Vec<(usize, u32)> {
+            stops.iter().filter_map(|(stop, walk)| Some((*self.stop_index.get(stop)?, *walk))).collect()
+        };
+        let (access, egress) = (dense(access), dense(egress));
+        let n = self.stops.len();
+        let rounds = max_transfers + 1;
+        // one slot past the stops stands for the destination point, so rounds still prune against it
+        let target = n;
+        let mut arrival: Vec<Vec<u32>> = vec![vec![UNREACHED; n + 1]; rounds + 1];
+        let mut labels: Vec<Vec<Option<Label>>> = vec![vec![None; n + 1]; rounds + 1];
+        let mut best = vec![UNREACHED; n + 1];
+        let mut marked = vec![false; n];
+        // egress stop giving the best arrival at the target in each round
+        let mut alighted: Vec<Option<usize>> = vec![None; rounds + 1];
+
+        for (stop, walk) in &access {
+            let time = departure + walk;
+            if time < arrival[0][*stop] {
+                arrival[0][*stop] = time;
+                best[*stop] = time;
+                marked[*stop] = true;
+            }
+        }
+        self.relax_transfers(0, &mut arrival, &mut labels, &mut best, &mut marked, target);
+
+        for k in 0..=rounds {
+            if k > 0 {
+                arrival[k] = arrival[k - 1].clone();
+                self.scan_round(k, &mut arrival, &mut labels, &mut best, &mut marked, target);
+            }
+            for (stop, walk) in &egress {
+                let time = arrival[k][*stop];
+                // an access stop is only worth stopping at once something was ridden or walked from it
+                let has_leg = (0..=k).rev().find_map(|round| labels[round][*stop]).is_some();
+                if time != UNREACHED && has_leg && time + walk < arrival[k][target] {
+                    arrival[k][target] = time + walk;
+                    best[target] = best[target].min(time + walk);
+                    alighted[k] = Some(*stop);
+                }
+            }
+            if k > 0 && !marked.iter().any(|marked| *marked) {
+                break;
+            }
+        }
+
+        let round = (0..=rounds).filter(|k| alighted[*k].is_some()).min_by_key(|k| (arrival[*k][target], *k))?;
+        Some(self.journey(departure, round, alighted[round]?, &arrival, &labels))
+    }
+
+    //rRAPTOR: the journeys leaving origin between from and to that no other beats on departure, arrival and
+    //transfers together, earliest departure first. departures are scanned latest first, keeping each round's
+    //arrivals, so an earlier departure only has to improve on what a later one already reached
+    pub fn range(&self, origin: &str, destination: &str, from: u32, to: u32, max_transfers: usize) -> Vec<Journey> {
+        let (Some(origin), Some(destination)) = (self.stop_index.get(origin).copied(), self.stop_index.get(destination).copied()) else {
+            return Vec::new();
+        };
+        let n = self.stops.len();
+        let rounds = max_transfers + 1;
+        let mut arrival: Vec<Vec<u32>> = vec![vec![UNREACHED; n]; rounds + 1];
+        let mut labels: Vec<Vec<Option<Label>>> = vec![vec![None; n]; rounds + 1];
+        let mut journeys = Vec::new();
+
+        for departure in self.departures(origin, from, to) {
+            let reached_before: Vec<u32> = arrival.iter().map(|round| round[destination]).collect();
+            let mut marked = vec![false; n];
+            arrival[0][origin] = departure;
+            marked[origin] = true;
+            // rounds prune against their own arrivals, an earlier round's are from a journey with fewer trips
+            let mut best = arrival[0].clone();
+            self.relax_transfers(0, &mut arrival, &mut labels, &mut best, &mut marked, destination);
+            for k in 1..=rounds {
+                for stop in 0..n {
+                    if arrival[k - 1][stop] < arrival[k][stop] {
+                        arrival[k][stop] = arrival[k - 1][stop];
+                        labels[k][stop] = None;
+                    }
+                }
+                let mut best = arrival[k].clone();
+                self.scan_round(k, &mut arrival, &mut labels, &mut best, &mut marked, destination);
+                if !marked.iter().any(|marked| *marked) {
+                    break;
+                }
+            }
+            // one more trip has to arrive earlier to be worth it
+            for k in 1..=rounds {
+                if arrival[k][destination] < reached_before[k] && arrival[k][destination] < arrival[k - 1][destination] {
+                    journeys.push(self.journey(departure, k, destination, &arrival, &labels));
+                }
+            }
+        }
+        journeys.sort_by_key(|journey| (journey.departure, journey.transfers));
+        journeys
+    }
+
+    // times a rider could leave origin in the window to catch a trip there or at the end of a footpath from it, latest first
+    fn departures(&self, origin: usize, from: u32, to: u32) -> Vec<u32> {
+        let mut departures = Vec::new();
+        for (stop, walk) in std::iter::once((origin, 0)).chain(self.transfers[origin].iter().copied()) {
+            for (pattern, position) in &self.stop_patterns[stop] {
+                for times in &self.patterns[*pattern].times {
+                    if let Some(departure) = times[*position].1.checked_sub(walk) {
+                        if departure >= from && departure <= to {
+                            departures.push(departure);
+                        }
+                    }
+                }
+            }
+        }
+        departures.sort_unstable_by(|a, b| b.cmp(a));
+        departures.dedup();
+        departures
+    }
+
+    // board trips at the stops marked last round, then walk on from the stops they reached
+    fn scan_round(&self, k: usize, arrival: &mut [Vec<u32>], labels: &mut [Vec<Option<Label>>], best: &mut [u32], marked: &mut [bool], destination: usize) {
+        // earliest marked position on each pattern
+        let mut queue: HashMap<usize, usize> = HashMap::new();
+        for (stop, is_marked) in marked.iter_mut().enumerate() {
+            if !*is_marked {
+                continue;
+            }
+            for (pattern, position) in &self.stop_patterns[stop] {
+                let entry = queue.entry(*pattern).or_insert(*position);
+                *entry = (*entry).min(*position);
+            }
+            *is_marked = false;
+        }
+
+        for (pattern_index, start) in queue {
+            let pattern = &self.patterns[pattern_index];
+            let mut trip: Option<(usize, usize)> = None;
+            for position in start..pattern.stops.len() {
+                let stop = pattern.stops[position];
+                if let Some((current, board)) = trip {
+                    let time = pattern.times[current][position].0;
+                    if time < best[stop] && time < best[destination] {
+                        arrival[k][stop] = time;
+                        best[stop] = time;
+                        labels[k][stop] = Some(Label::Transit { pattern: pattern_index, trip: current, board, alight: position });
+                        marked[stop] = true;
+                    }
+                }
+                // a trip from the previous round's arrival here may be earlier than the one ridden
+                let previous = arrival[k - 1][stop];
+                if previous == UNREACHED {
+                    continue;
+                }
+                let earlier = if self.ruled_boarding[stop] {
+                    self.ruled_trip(pattern_index, position, k - 1, previous, labels)
+                } else {
+                    pattern.earliest_trip(position, self.ready(k - 1, stop, previous, labels))
+                };
+                if let Some(earlier) = earlier {
+                    if trip.is_none_or(|(current, _)| earlier < current) {
+                        trip = Some((earlier, position));
+                    }
+                }
+            }
+        }
+
+        self.relax_transfers(k, arrival, labels, best, marked, destination);
+    }
+
+    // riders who came in on a vehicle need the stop's change time before boarding another
+    fn ready(&self, k: usize, stop: usize, time: u32, labels: &[Vec<Option<Label>>]) -> u32 {
+        let by_vehicle = (0..=k).rev().find_map(|round| labels[round][stop]).is_some_and(|label| matches!(label, Label::Transit { .. }));
+        if by_vehicle { time.saturating_add(self.change[stop]) } else { time }
+    }
+
+    // earliest trip of the pattern a rider there at time can take, where transfers.txt rules changes onto some trips
+    fn ruled_trip(&self, pattern: usize, position: usize, k: usize, time: u32, labels: &[Vec<Option<Label>>]) -> Option<usize> {
+        let stop = self.patterns[pattern].stops[position];
+        let ready = self.ready(k, stop, time, labels);
+        let alighted = self.alighted(k, stop, labels);
+        let times = &self.patterns[pattern].times;
+        let first = self.patterns[pattern].earliest_trip(position, time)?;
+        (first..times.len()).find(|trip| {
+            let departure = times[*trip][position].1;
+            let ruled = alighted.and_then(|(from, from_trip, at)| Some((at, *self.ruled.get(&(from, stop, from_trip, (pattern, *trip)))?)));
+            match ruled {
+                Some((at, Some(seconds))) => departure >= time.max(at.saturating_add(seconds)),
+                Some((_, None)) => false,
+                None => departure >= ready,
+            }
+        })
+    }
+
+    // stop, trip and time of the last alighting before being at stop in round k, walking on after it or not
+    fn alighted(&self, k: usize, stop: usize, labels: &[Vec<Option<Label>>]) -> Option<(usize, Trip, u32)> {
+        let last = |stop: usize| (0..=k).rev().find_map(|round| labels[round][stop]);
+        let (stop, label) = match last(stop)? {
+            Label::Transfer { from, .. } => (from, last(from)?),
+            label => (stop, label),
+        };
+        match label {
+            Label::Transit { pattern, trip, alight, .. } => Some((stop, (pattern, trip), self.patterns[pattern].times[trip][alight].0)),
+            Label::Transfer { .. } => None,
+        }
+    }
+
+    fn relax_transfers(&self, k: usize, arrival: &mut [Vec<u32>], labels: &mut [Vec<Option<Label>>], best: &mut [u32], marked: &mut [bool], destination: usize) {
+        let from_stops: Vec<usize> = (0..self.stops.len()).filter(|stop| marked[*stop]).collect();
+        for from in from_stops {
+            for (to, seconds) in &self.transfers[from] {
+                let time = arrival[k][from] + seconds;
+                if time < best[*to] && time < best[destination] {
+                    arrival[k][*to] = time;
+                    best[*to] = time;
+                    labels[k][*to] = Some(Label::Transfer { from, seconds: *seconds });
+                    marked[*to] = true;
+                }
+            }
+        }
+    }
+
+    fn journey(&self, departure: u32, mut round: usize, destination: usize, arrival: &[Vec<u32>], labels: &[Vec<Option<Label>>]) -> Journey {
+        let arrival_time = arrival[round][destination];
+        let mut legs = Vec::new();
+        let mut stop = destination;
+        loop {
+            // rounds that did not improve a stop keep the label of an earlier round
+            while labels[round][stop].is_none() && round > 0 {
+                round -= 1;
+            }
+            match labels[round][stop] {
+                Some(Label::Transit { pattern, trip, board, alight }) => {
+                    let pattern = &self.patterns[pattern];
+                    legs.push(Leg::Transit {
+                        route_id: pattern.route_id.clone(),
+                        trip_id: pattern.trip_ids[trip].clone(),
+                        board_stop: self.stops[pattern.stops[board]].clone(),
+                        board_time: pattern.times[trip][board].1,
+                        alight_stop: self.stops[pattern.stops[alight]].clone(),
+                        alight_time: pattern.times[trip][alight].0,
+                    });
+                    stop = pattern.stops[board];
+                    round -= 1;
+                }
+                Some(Label::Transfer { from, seconds }) => {
+                    let arrival = arrival[round][stop];
+                    legs.push(Leg::Transfer { from_stop: self.stops[from].clone(), to_stop: self.stops[stop].clone(), departure: arrival - seconds, arrival });
+                    stop = from;
+                }
+                None => break,
+            }
+        }
+        legs.reverse();
+        let transfers = legs.iter().filter(|leg| matches!(leg, Leg::Transit { .. })).count().saturating_sub(1);
+        Journey { departure, arrival: arrival_time, transfers, legs }
+    }
+}
+
+//timetables for the router tests
+#[cfg(test)]
+pub mod fixtures {
+    use crate::graph::{GTFSGraph, StopTimes};
+
+    pub fn hms(h: u32, m: u32) -> u32 {
+        h * 3600 + m * 60
+    }
+
+    //stop times arriving and leaving at once
+    pub fn trip(stops: &[(&str, u32)]) -> StopTimes {
+        stops.iter().map(|(stop, time)| (stop.to_string(), *time, *time)).collect()
+    }
+
+    //a fast line A-B-C-D with a change at B onto a slow line B-E, and a direct but late E express from A
+    pub fn feed() -> GTFSGraph {
+        let mut gtfs = GTFSGraph::new("test");
+        gtfs.add_trip("red-1".to_string(), "red".to_string(), "weekday".to_string(), trip(&[("A", hms(8, 0)), ("B", hms(8, 10)), ("C", hms(8, 20)), ("D", hms(8, 30))]));
+        gtfs.add_trip("red-2".to_string(), "red".to_string(), "weekday".to_string(), trip(&[("A", hms(8, 30)), ("B", hms(8, 40)), ("C", hms(8, 50)), ("D", hms(9, 0))]));
+        gtfs.add_trip("blue-1".to_string(), "blue".to_string(), "weekday".to_string(), trip(&[("B", hms(8, 15)), ("E", hms(8, 35))]));
+        gtfs.add_trip("express-1".to_string(), "express".to_string(), "weekday".to_string(), trip(&[("A", hms(8, 5)), ("E", hms(8, 50))]));
+        gtfs
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::fixtures::{feed, hms, trip};
+    use super::{Journey, Leg, Raptor};
+    use crate::graph::format_time;
+    use crate::transfers::{StationRules, TransferRule, TransferType};
+
+    #[test]
+    fn test_earliest_arrival_and_max_transfers() {
+        let raptor = Raptor::new(&feed());
+        let journey = raptor.earliest_arrival("A", "E", hms(7, 55), 2).unwrap();
+        assert_eq!(journey.arrival, hms(8, 35));
+        assert_eq!(journey.transfers, 1);
+        assert_eq!(journey.legs.len(), 2);
+        assert_eq!(journey.legs[0], Leg::Transit {
+            route_id: "red".to_string(),
+            trip_id: "red-1".to_string(),
+            board_stop: "A".to_string(),
+            board_time: hms(8, 0),
+            alight_stop: "B".to_string(),
+            alight_time: hms(8, 10),
+        });
+        assert!(matches!(&journey.legs[1], Leg::Transit { trip_id, board_stop, .. } if trip_id == "blue-1" && board_stop == "B"));
+
+        // without a transfer only the express gets there
+        let direct = raptor.earliest_arrival("A", "E", hms(7, 55), 0).unwrap();
+        assert_eq!(direct.arrival, hms(8, 50));
+        assert_eq!(direct.legs.len(), 1);
+
+        // too late for the first red trip, the second one misses the blue line
+        assert!(raptor.earliest_arrival("A", "E", hms(8, 6), 3).is_none());
+        assert_eq!(raptor.earliest_arrival("A", "D", hms(8, 6), 0).unwrap().arrival, hms(9, 0));
+    }
+
+    #[test]
+    fn test_footpath_transfer() {
+        let mut raptor = Raptor::new(&feed());
+        raptor.add_transfer("C", "E", 600);
+        let journey = raptor.earliest_arrival("A", "E", hms(7, 55), 1).unwrap();
+        assert_eq!(journey.arrival, hms(8, 30));
+        assert_eq!(journey.transfers, 0);
+        assert!(matches!(&journey.legs[1], Leg::Transfer { from_stop, departure, .. } if from_stop == "C" && *departure == hms(8, 20)));
+        assert_eq!(format_time(hms(25, 3) + 7), "25:03:07");
+    }
+
+    #[test]
+    fn test_range_query() {
+        let mut raptor = Raptor::new(&feed());
+        let summary = |journeys: Vec<Journey>| journeys.iter().map(|journey| (journey.departure, journey.arrival, journey.transfers)).collect::<Vec<_>>();
+        // the 8:30 red trip gets nowhere near E, the 8:00 one is faster than the express but needs a change
+        assert_eq!(summary(raptor.range("A", "E", hms(7, 0), hms(10, 0), 3)), vec![(hms(8, 0), hms(8, 35), 1), (hms(8, 5), hms(8, 50), 0)]);
+        assert_eq!(summary(raptor.range("A", "E", hms(7, 0), hms(10, 0), 0)), vec![(hms(8, 5), hms(8, 50), 0)]);
+        assert!(raptor.range("A", "E", hms(8, 6), hms(10, 0), 3).is_empty());
+
+        // with the walk the 8:00 red trip needs no change and beats its own 8:35 with one
+        raptor.add_transfer("C", "E", 600);
+        let journeys = raptor.range("A", "E", hms(7, 0), hms(10, 0), 3);
+        assert_eq!(summary(journeys.clone()), vec![(hms(8, 0), hms(8, 30), 0), (hms(8, 5), hms(8, 50), 0), (hms(8, 30), hms(9, 0), 0)]);
+        assert!(matches!(&journeys[2].legs[0], Leg::Transit { trip_id, .. } if trip_id == "red-2"));
+        for journey in &journeys {
+            assert_eq!(Some(journey.arrival), raptor.earliest_arrival("A", "E", journey.departure, 3).map(|found| found.arrival));
+        }
+    }
+
+    #[test]
+    fn test_trip_level_transfer_rules() {
+        let trips = |from: &str, to: &str, transfer_type: TransferType| TransferRule {
+            from_stop: Some("B".to_string()),
+            to_stop: Some("B".to_string()),
+            from_route: None,
+            to_route: None,
+            from_trip: Some(from.to_string()),
+            to_trip: Some(to.to_string()),
+            transfer_type,
+            min_transfer_time: None,
+        };
+        // the change from red-1 to blue-1 is fine at B until a rule for those two trips rules it out
+        let mut gtfs = feed();
+        gtfs.add_station_rules(StationRules { transfers: vec![trips("red-1", "blue-1", TransferType::NotPossible)], ..Default::default() });
+        let journey = Raptor::new(&gtfs).earliest_arrival("A", "E", hms(7, 55), 2).unwrap();
+        assert_eq!(journey.arrival, hms(8, 50));
+        assert_eq!(journey.transfers, 0);
+
+        // blue-1 waiting for red-1 beats a change time at B that would miss it
+        let mut gtfs = feed();
+        gtfs.transfers.insert(("B".to_string(), "B".to_string()), 600);
+        assert_eq!(Raptor::new(&gtfs).earliest_arrival("A", "E", hms(7, 55), 2).unwrap().arrival, hms(8, 50));
+        gtfs.add_station_rules(StationRules { transfers: vec![trips("red-1", "blue-1", TransferType::Timed)], ..Default::default() });
+        let raptor = Raptor::new(&gtfs);
+        assert_eq!(raptor.earliest_arrival("A", "E", hms(7, 55), 2).unwrap().arrival, hms(8, 35));
+        assert_eq!(raptor.range("A", "E", hms(7, 0), hms(10, 0), 3).first().map(|journey| journey.arrival), Some(hms(8, 35)));
+    }
+
+    #[test]
+    fn test_one_service_day() {
+        let date = |d: u32| chrono::NaiveDate::from_ymd_opt(2024, 1, d).unwrap();
+        let mut gtfs = feed();
+        gtfs.calendar.add_service("weekday".to_string(), [true, true, true, true, true, false, false], date(1), date(31));
+        gtfs.calendar.add_service("tuesday".to_string(), [false, true, false, false, false, false, false], date(1), date(31));
+        gtfs.add_trip("green-1".to_string(), "green".to_string(), "tuesday".to_string(), trip(&[("C", hms(8, 25)), ("E", hms(8, 40))]));
+        // loaded for monday and tuesday together, tuesday's green trip takes monday's riders on from C
+        assert_eq!(Raptor::new(&gtfs).earliest_arrival("C", "E", hms(8, 0), 2).map(|journey| journey.arrival), Some(hms(8, 40)));
+        assert!(Raptor::new(&gtfs.service_day(date(15))).earliest_arrival("C", "E", hms(8, 0), 2).is_none());
+        assert_eq!(Raptor::new(&gtfs.service_day(date(16))).earliest_arrival("C", "E", hms(8, 0), 2).map(|journey| journey.arrival), Some(hms(8, 40)));
+        assert!(gtfs.service_day(date(20)).trips.is_empty());
+    }
+}
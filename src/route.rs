@@ -0,0 +1,59 @@
+use std::time::Instant;
+mod graph;
+mod calendar;
+mod transfers;
+mod geodesic;
+mod interner;
+mod routing;
+mod astar;
+mod alternatives;
+mod directions;
+use graph::Graph;
+use routing::{Profile, Router};
+
+fn main() {
+    let args = arguments::parse(std::env::args()).unwrap();
+    let edges = args.get::<String>("edges").unwrap_or_else(|| "testedges.csv".to_string());
+    let nodes = args.get::<String>("nodes").unwrap_or_else(|| "testnodes.csv".to_string());
+    let profile = args.get::<Profile>("profile").unwrap_or(Profile::Foot);
+    let source = args.get::<u64>("from").unwrap_or(2729443585);
+    let target = args.get::<u64>("to").unwrap_or(2729463686);
+    let count = args.get::<usize>("alternatives").unwrap_or(1);
+    let streets = match args.get::<String>("streets") {
+        Some(file) => directions::StreetInfo::from_csv(&file).unwrap(),
+        None => directions::StreetInfo::default(),
+    };
+
+    let start_time = Instant::now();
+    let graph = Graph::from_csv(&edges, &nodes);
+    eprintln!("from_csv took {:?}", start_time.elapsed().as_secs_f64());
+    let start_time = Instant::now();
+    let router = Router::new(&graph, profile);
+    eprintln!("Router::new took {:?}", start_time.elapsed().as_secs_f64());
+
+    let (Some(source), Some(target)) = (router.node_index(source), router.node_index(target)) else {
+        println!("Unknown node");
+        return;
+    };
+    let (_, dijkstra_stats) = router.shortest_path_with_stats(source, target);
+    println!("dijkstra settled {} nodes, took {:?}ns", dijkstra_stats.settled, dijkstra_stats.elapsed.as_nanos());
+    let (route, astar_stats) = astar::astar(&router, source, target);
+    println!("astar settled {} nodes, took {:?}ns", astar_stats.settled, astar_stats.elapsed.as_nanos());
+    match route {
+        Some(route) => {
+            println!("distance: {:.1} m, duration: {:.1} s", route.distance, route.duration);
+            println!("{:?}", route.edge_ids(&graph));
+            for maneuver in directions::directions(&graph, &route, &streets).maneuvers {
+                println!("{:.0} m: {}", maneuver.distance, maneuver.instruction());
+            }
+        }
+        None => println!("No path found"),
+    }
+
+    if count > 1 {
+        let options = alternatives::AlternativeOptions { count, ..Default::default() };
+        for (i, alternative) in alternatives::alternatives(&graph, &router, source, target, &options).iter().enumerate() {
+            println!("alternative {}: distance: {:.1} m, duration: {:.1} s, shared with best: {:.2}", i, alternative.route.distance, alternative.route.duration, alternative.shared_with_best);
+        }
+    }
+}
@@ -0,0 +1,375 @@
+use std::{collections::{BinaryHeap, HashSet}, cmp::Ordering, str::FromStr, time::{Duration, Instant}};
+use serde::{Serialize, Deserialize};
+use crate::graph::{Edge, Graph, Node};
+use crate::interner::NodeInterner;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Profile {
+    Foot,
+    Bike,
+    Car,
+}
+
+// km/h of the fastest osm4routing car class
+const MOTORWAY_KMH: f64 = 105.0;
+
+impl Profile {
+    //upper bound on speed() over every edge, in metres per second
+    pub fn max_speed(&self) -> f64 {
+        match self {
+            Profile::Foot => 1.4,
+            Profile::Bike => 4.5,
+            Profile::Car => MOTORWAY_KMH / 3.6,
+        }
+    }
+
+    //metres per second the profile travels along the edge, None if the direction is not allowed
+    pub fn speed(&self, edge: &Edge, forward: bool) -> Option<f64> {
+        match self {
+            Profile::Foot => if edge.foot { Some(self.max_speed()) } else { None },
+            Profile::Bike => {
+                let allowed = if forward { edge.bike_forward } else { edge.bike_backward };
+                if allowed { Some(self.max_speed()) } else { None }
+            }
+            Profile::Car => {
+                let class = if forward { &edge.car_forward } else { &edge.car_backward };
+                // km/h per osm4routing car accessibility class
+                let kmh = match class.as_str() {
+                    "Forbidden" => return None,
+                    "Residential" => 30.0,
+                    "Tertiary" => 40.0,
+                    "Secondary" => 50.0,
+                    "Primary" => 65.0,
+                    "Trunk" => 80.0,
+                    "Motorway" => MOTORWAY_KMH,
+                    _ => 30.0,
+                };
+                Some(kmh / 3.6)
+            }
+        }
+    }
+}
+
+impl FromStr for Profile {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "foot" | "walk" => Ok(Profile::Foot),
+            "bike" | "bicycle" => Ok(Profile::Bike),
+            "car" | "drive" => Ok(Profile::Car),
+            _ => Err(format!("unknown profile {}", s)),
+        }
+    }
+}
+
+//one traversable direction of an Edge
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Arc {
+    pub head: usize,
+    //index into Graph::edges
+    pub edge: usize,
+    //true when travelling from the edge source to its target
+    pub forward: bool,
+    pub length: f64,
+    pub duration: f64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Route {
+    //metres
+    pub distance: f64,
+    //seconds
+    pub duration: f64,
+    //(index into Graph::edges, travelled forward) in travel order
+    pub edges: Vec<(usize, bool)>,
+}
+
+impl Route {
+    pub fn edge_ids(&self, graph: &Graph) -> Vec<String> {
+        self.edges.iter().map(|(edge, _)| graph.edges[*edge].id.clone()).collect()
+    }
+
+    //edge linestrings joined in travel order, reversing edges walked backwards
+    pub fn geometry(&self, graph: &Graph) -> Vec<Node> {
+        let mut geometry: Vec<Node> = Vec::new();
+        for (edge, forward) in &self.edges {
+            let mut linestring = graph.edges[*edge].linestring.clone();
+            if !forward {
+                linestring.reverse();
+            }
+            // consecutive edges share their joining node
+            let skip = if geometry.is_empty() { 0 } else { 1 };
+            geometry.extend(linestring.into_iter().skip(skip));
+        }
+        geometry
+    }
+}
+
+//priority queue entry, ordered so BinaryHeap pops the smallest cost first
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct State {
+    pub cost: f64,
+    pub node: usize,
+}
+
+impl Eq for State {}
+
+impl Ord for State {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.cost.total_cmp(&self.cost).then_with(|| self.node.cmp(&other.node))
+    }
+}
+
+impl PartialOrd for State {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub struct SearchStats {
+    pub settled: usize,
+    pub elapsed: Duration,
+}
+
+//result of a one-to-many search, indexed by dense node index
+#[derive(Debug, Clone)]
+pub struct Search {
+    pub cost: Vec<f64>,
+    //(tail node, arc) used to reach each node
+    pub parent: Vec<Option<(usize, Arc)>>,
+    pub settled: usize,
+}
+
+impl Search {
+    pub fn reached(&self, node: usize) -> bool {
+        self.cost[node].is_finite()
+    }
+
+    //arcs from the search origin to node, in travel order
+    pub fn path_to(&self, node: usize) -> Vec<Arc> {
+        let mut path = Vec::new();
+        let mut current = node;
+        while let Some((tail, arc)) = self.parent[current] {
+            path.push(arc);
+            current = tail;
+        }
+        path.reverse();
+        path
+    }
+
+    //the seeded source the path to node starts from
+    pub fn origin(&self, node: usize) -> usize {
+        let mut current = node;
+        while let Some((tail, _)) = self.parent[current] {
+            current = tail;
+        }
+        current
+    }
+}
+
+//adjacency view of Graph for a single travel profile
+#[derive(Debug, Clone)]
+pub struct Router {
+    pub profile: Profile,
+    //osm node id <-> dense index, shared numbering with every other consumer of the same Graph
+    pub nodes: NodeInterner,
+    //(lon, lat) per dense index
+    pub coords: Vec<(f64, f64)>,
+    pub arcs: Vec<Vec<Arc>>,
+}
+
+impl Router {
+    pub fn new(graph: &Graph, profile: Profile) -> Self {
+        let nodes = graph.interner.clone();
+        let mut coords: Vec<Option<(f64, f64)>> = vec![None; nodes.len()];
+        let mut arcs: Vec<Vec<Arc>> = vec![Vec::new(); nodes.len()];
+        for node in &graph.nodes {
+            let index = nodes.get(node.id).unwrap();
+            coords[index].get_or_insert((node.lon, node.lat));
+        }
+        for (index, edge) in graph.edges.iter().enumerate() {
+            let (Some(source), Some(target)) = (nodes.get_str(&edge.source), nodes.get_str(&edge.target)) else {
+                continue;
+            };
+            // endpoints missing from the node file fall back to the linestring ends
+            if let Some(first) = edge.linestring.first() {
+                coords[source].get_or_insert((first.lon, first.lat));
+            }
+            if let Some(last) = edge.linestring.last() {
+                coords[target].get_or_insert((last.lon, last.lat));
+            }
+            if coords[source].is_none() || coords[target].is_none() {
+                continue;
+            }
+            if let Some(speed) = profile.speed(edge, true) {
+                arcs[source].push(Arc { head: target, edge: index, forward: true, length: edge.length, duration: edge.length / speed });
+            }
+            if let Some(speed) = profile.speed(edge, false) {
+                arcs[target].push(Arc { head: source, edge: index, forward: false, length: edge.length, duration: edge.length / speed });
+            }
+        }
+        Self {
+            profile,
+            nodes,
+            // only endpoints of skipped edges are left without a position
+            coords: coords.into_iter().map(Option::unwrap_or_default).collect(),
+            arcs,
+        }
+    }
+
+    //same nodes with every arc turned around, searching it finds costs to a node instead of from it
+    pub fn reversed(&self) -> Router {
+        let mut arcs: Vec<Vec<Arc>> = vec![Vec::new(); self.node_count()];
+        for (tail, out) in self.arcs.iter().enumerate() {
+            for arc in out {
+                arcs[arc.head].push(Arc { head: tail, ..*arc });
+            }
+        }
+        Router {
+            profile: self.profile,
+            nodes: self.nodes.clone(),
+            coords: self.coords.clone(),
+            arcs,
+        }
+    }
+
+    pub fn node_count(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn node_index(&self, osm_id: u64) -> Option<usize> {
+        self.nodes.get(osm_id)
+    }
+
+    //fastest route between two osm node ids
+    pub fn route(&self, source: u64, target: u64) -> Option<Route> {
+        self.shortest_path(self.node_index(source)?, self.node_index(target)?)
+    }
+
+    pub fn shortest_path(&self, source: usize, target: usize) -> Option<Route> {
+        self.shortest_path_with_stats(source, target).0
+    }
+
+    pub fn shortest_path_with_stats(&self, source: usize, target: usize) -> (Option<Route>, SearchStats) {
+        let start_time = Instant::now();
+        let search = self.dijkstra(&[(source, 0.0)], Some(target), f64::INFINITY, |arc| arc.duration);
+        let route = if search.reached(target) { Some(Self::to_route(search.path_to(target))) } else { None };
+        (route, SearchStats { settled: search.settled, elapsed: start_time.elapsed() })
+    }
+
+    pub fn to_route(path: Vec<Arc>) -> Route {
+        Route {
+            distance: path.iter().map(|arc| arc.length).sum(),
+            duration: path.iter().map(|arc| arc.duration).sum(),
+            edges: path.iter().map(|arc| (arc.edge, arc.forward)).collect(),
+        }
+    }
+
+    //dijkstra from several seeded sources, stopping once target is settled or costs pass limit
+    pub fn dijkstra<F>(&self, sources: &[(usize, f64)], target: Option<usize>, limit: f64, cost: F) -> Search where F: Fn(&Arc) -> f64 {
+        self.search_until(sources, limit, cost, |node| Some(node) == target)
+    }
+
+    //dijkstra that stops once every target is settled or costs pass limit
+    pub fn one_to_many(&self, sources: &[(usize, f64)], targets: &[usize], limit: f64) -> Search {
+        let mut remaining: HashSet<usize> = targets.iter().copied().collect();
+        self.search_until(sources, limit, |arc| arc.duration, |node| {
+            remaining.remove(&node);
+            remaining.is_empty()
+        })
+    }
+
+    //dijkstra that stops after settling a node for which done returns true, or once costs pass limit
+    pub fn search_until<F, D>(&self, sources: &[(usize, f64)], limit: f64, cost: F, mut done: D) -> Search where F: Fn(&Arc) -> f64, D: FnMut(usize) -> bool {
+        let mut search = Search {
+            cost: vec![f64::INFINITY; self.node_count()],
+            parent: vec![None; self.node_count()],
+            settled: 0,
+        };
+        let mut heap = BinaryHeap::new();
+        for (source, initial) in sources {
+            if *initial < search.cost[*source] {
+                search.cost[*source] = *initial;
+                heap.push(State { cost: *initial, node: *source });
+            }
+        }
+        while let Some(State { cost: current, node }) = heap.pop() {
+            if current > search.cost[node] {
+                continue;
+            }
+            if current > limit {
+                break;
+            }
+            search.settled += 1;
+            if done(node) {
+                break;
+            }
+            for arc in &self.arcs[node] {
+                let next = current + cost(arc);
+                if next < search.cost[arc.head] {
+                    search.cost[arc.head] = next;
+                    search.parent[arc.head] = Some((node, *arc));
+                    heap.push(State { cost: next, node: arc.head });
+                }
+            }
+        }
+        search
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Profile, Router};
+    use crate::graph::fixtures::path;
+    use crate::graph::{Edge, Graph, Node};
+    use approx::assert_relative_eq;
+
+    fn edge(graph: &mut Graph, id: &str, source: u64, target: u64, length: f64, car: (&str, &str), bike_backward: bool) {
+        let a = graph.nodes.iter().find(|node| node.id == source).copied().unwrap();
+        let b = graph.nodes.iter().find(|node| node.id == target).copied().unwrap();
+        graph.add_edge_obj(Edge { car_forward: car.0.to_string(), car_backward: car.1.to_string(), bike_backward, ..path(id, a, b, length) });
+    }
+
+    // 1 -> 2 -> 3 is a one way primary road, 1 - 3 a footpath
+    fn triangle() -> Graph {
+        let mut graph = Graph::new();
+        graph.add_node_obj(Node::new(1, -118.0, 34.0));
+        graph.add_node_obj(Node::new(2, -118.0, 34.001));
+        graph.add_node_obj(Node::new(3, -118.001, 34.0));
+        edge(&mut graph, "a", 1, 2, 111.0, ("Primary", "Forbidden"), false);
+        edge(&mut graph, "b", 2, 3, 150.0, ("Primary", "Forbidden"), false);
+        edge(&mut graph, "c", 1, 3, 92.0, ("Forbidden", "Forbidden"), true);
+        graph
+    }
+
+    #[test]
+    fn test_profiles_respect_access() {
+        let graph = triangle();
+        let foot = Router::new(&graph, Profile::Foot).route(3, 1).unwrap();
+        assert_eq!(foot.edge_ids(&graph), vec!["c"]);
+        assert_relative_eq!(foot.duration, 92.0 / 1.4);
+
+        let car = Router::new(&graph, Profile::Car);
+        let forward = car.route(1, 3).unwrap();
+        assert_eq!(forward.edges, vec![(0, true), (1, true)]);
+        assert_relative_eq!(forward.distance, 261.0);
+        assert!(car.route(3, 1).is_none());
+
+        // the bike can only ride the one way roads forwards, so 2 -> 1 must go round by the path
+        let bike = Router::new(&graph, Profile::Bike).route(2, 1).unwrap();
+        assert_eq!(bike.edges, vec![(1, true), (2, false)]);
+    }
+
+    #[test]
+    fn test_route_on_test_edges() {
+        let graph = Graph::from_csv("testedges.csv", "testnodes.csv");
+        let router = Router::new(&graph, Profile::Foot);
+        let route = router.route(2729443585, 2729463686).unwrap();
+        let length: f64 = route.edges.iter().map(|(edge, _)| graph.edges[*edge].length).sum();
+        assert_relative_eq!(route.distance, length);
+        assert_relative_eq!(route.duration, length / 1.4);
+        assert!(Router::new(&graph, Profile::Car).route(2729443585, 2729463686).is_none());
+    }
+}
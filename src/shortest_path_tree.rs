@@ -0,0 +1,40 @@
+use std::{fs, time::Instant};
+mod graph;
+mod calendar;
+mod transfers;
+mod geodesic;
+mod interner;
+mod routing;
+mod tree;
+use graph::Graph;
+use routing::{Profile, Router};
+use tree::ShortestPathTree;
+
+fn main() {
+    let args = arguments::parse(std::env::args()).unwrap();
+    let edges = args.get::<String>("edges").unwrap_or_else(|| "testedges.csv".to_string());
+    let nodes = args.get::<String>("nodes").unwrap_or_else(|| "testnodes.csv".to_string());
+    let profile = args.get::<Profile>("profile").unwrap_or(Profile::Foot);
+    let origin = args.get::<u64>("from").unwrap_or(2729443585);
+    let cutoff = args.get::<f64>("cutoff");
+    let output = args.get::<String>("output").unwrap_or_else(|| "tree.csv".to_string());
+
+    let start_time = Instant::now();
+    let graph = Graph::from_csv(&edges, &nodes);
+    let router = Router::new(&graph, profile);
+    eprintln!("loading took {:?}", start_time.elapsed().as_secs_f64());
+
+    let Some(origin) = router.node_index(origin) else {
+        println!("Unknown node");
+        return;
+    };
+    let start_time = Instant::now();
+    let tree = ShortestPathTree::new(&graph, &router, origin, cutoff);
+    eprintln!("{} nodes reached from {}, took {:?}", tree.nodes.len(), tree.origin, start_time.elapsed().as_secs_f64());
+
+    if output.ends_with(".geojson") || output.ends_with(".json") {
+        fs::write(&output, tree.to_geojson().to_string()).unwrap();
+    } else {
+        tree.to_csv(&output).unwrap();
+    }
+}
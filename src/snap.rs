@@ -0,0 +1,313 @@
+use geographiclib_rs::{Geodesic, DirectGeodesic, InverseGeodesic};
+use itertools::Itertools;
+use crate::graph::{Edge, Graph, Node, RadiusBasedNeighborhood};
+use crate::routing::{Route, Router};
+
+//a point projected onto an edge of the street graph
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Snap {
+    //index into Graph::edges
+    pub edge: usize,
+    pub lon: f64,
+    pub lat: f64,
+    //metres along the linestring from the edge source
+    pub offset: f64,
+    //metres from the query point to the projected point
+    pub distance: f64,
+}
+
+impl Snap {
+    fn fraction(&self, graph: &Graph) -> f64 {
+        let length = linestring_length(&graph.edges[self.edge].linestring);
+        if length > 0.0 { (self.offset / length).clamp(0.0, 1.0) } else { 0.0 }
+    }
+
+    //(node, seconds) to leave the snapped point through either end of its edge
+    pub fn departures(&self, graph: &Graph, router: &Router) -> Vec<(usize, f64)> {
+        let edge = &graph.edges[self.edge];
+        let fraction = self.fraction(graph);
+        let mut departures = Vec::new();
+        if let (Some(speed), Some(target)) = (router.profile.speed(edge, true), router.nodes.get_str(&edge.target)) {
+            departures.push((target, (1.0 - fraction) * edge.length / speed));
+        }
+        if let (Some(speed), Some(source)) = (router.profile.speed(edge, false), router.nodes.get_str(&edge.source)) {
+            departures.push((source, fraction * edge.length / speed));
+        }
+        departures
+    }
+
+    //(node, seconds) to reach the snapped point from either end of its edge
+    pub fn arrivals(&self, graph: &Graph, router: &Router) -> Vec<(usize, f64)> {
+        let edge = &graph.edges[self.edge];
+        let fraction = self.fraction(graph);
+        let mut arrivals = Vec::new();
+        if let (Some(speed), Some(source)) = (router.profile.speed(edge, true), router.nodes.get_str(&edge.source)) {
+            arrivals.push((source, fraction * edge.length / speed));
+        }
+        if let (Some(speed), Some(target)) = (router.profile.speed(edge, false), router.nodes.get_str(&edge.target)) {
+            arrivals.push((target, (1.0 - fraction) * edge.length / speed));
+        }
+        arrivals
+    }
+
+    //seconds to travel straight along a shared edge to other, if the profile allows that direction
+    pub fn direct(&self, other: &Snap, graph: &Graph, router: &Router) -> Option<f64> {
+        if self.edge != other.edge {
+            return None;
+        }
+        let edge = &graph.edges[self.edge];
+        let forward = other.offset >= self.offset;
+        let speed = router.profile.speed(edge, forward)?;
+        Some((other.fraction(graph) - self.fraction(graph)).abs() * edge.length / speed)
+    }
+}
+
+//door to door route between two snapped points, the partial edges at either end included
+#[derive(Debug, Clone)]
+pub struct SnappedRoute {
+    //metres
+    pub distance: f64,
+    //seconds
+    pub duration: f64,
+    //edges between the two partial ones, empty when both points are on the same edge
+    pub route: Route,
+    pub geometry: Vec<Node>,
+}
+
+impl Snap {
+    //fastest way from this point to other, leaving and joining the graph along the snapped edges
+    pub fn route_to(&self, other: &Snap, graph: &Graph, router: &Router) -> Option<SnappedRoute> {
+        let arrivals = other.arrivals(graph, router);
+        let targets: Vec<usize> = arrivals.iter().map(|(node, _)| *node).collect();
+        let search = router.one_to_many(&self.departures(graph, router), &targets, f64::INFINITY);
+        let best = arrivals.iter()
+            .map(|(node, cost)| (*node, search.cost[*node] + cost))
+            .min_by(|a, b| a.1.total_cmp(&b.1));
+
+        if let Some(duration) = self.direct(other, graph, router) {
+            if best.is_none_or(|(_, through)| duration <= through) {
+                let edge = &graph.edges[self.edge];
+                let mut geometry = cut_linestring(&edge.linestring, self.offset.min(other.offset), self.offset.max(other.offset));
+                if other.offset < self.offset {
+                    geometry.reverse();
+                }
+                let distance = (other.fraction(graph) - self.fraction(graph)).abs() * edge.length;
+                return Some(SnappedRoute { distance, duration, route: Router::to_route(Vec::new()), geometry });
+            }
+        }
+
+        let (node, duration) = best.filter(|(_, duration)| duration.is_finite())?;
+        let route = Router::to_route(search.path_to(node));
+        let (start, start_length) = self.leg(graph, router, search.origin(node), true);
+        let (end, end_length) = other.leg(graph, router, node, false);
+        let mut geometry = start;
+        for part in [route.geometry(graph), end] {
+            // consecutive parts share their joining node
+            let skip = if geometry.is_empty() { 0 } else { 1 };
+            geometry.extend(part.into_iter().skip(skip));
+        }
+        // a point snapped onto a node leaves a zero length leg
+        geometry.dedup_by(|a, b| a.lon == b.lon && a.lat == b.lat);
+        Some(SnappedRoute { distance: start_length + route.distance + end_length, duration, route, geometry })
+    }
+
+    //geometry and metres between the snapped point and node, an end of its edge, in travel order
+    fn leg(&self, graph: &Graph, router: &Router, node: usize, leaving: bool) -> (Vec<Node>, f64) {
+        let edge = &graph.edges[self.edge];
+        let fraction = self.fraction(graph);
+        let length = linestring_length(&edge.linestring);
+        let at_target = router.nodes.get_str(&edge.target) == Some(node);
+        let (mut geometry, metres) = if at_target {
+            (cut_linestring(&edge.linestring, self.offset, length), (1.0 - fraction) * edge.length)
+        } else {
+            (cut_linestring(&edge.linestring, 0.0, self.offset), fraction * edge.length)
+        };
+        // cut_linestring runs source to target
+        if leaving != at_target {
+            geometry.reverse();
+        }
+        (geometry, metres)
+    }
+}
+
+//index points are placed at most this many metres apart along every segment
+const INDEX_SPACING: f64 = 20.0;
+
+//vp-tree over every linestring vertex and points splitting longer segments, each carrying the index of its edge in Node::id
+pub struct EdgeSnapper {
+    vertices: Vec<Node>,
+    tree: vpsearch::Tree<Node>,
+}
+
+impl EdgeSnapper {
+    pub fn new(graph: &Graph) -> Self {
+        let geod = Geodesic::wgs84();
+        let mut vertices: Vec<Node> = Vec::new();
+        for (index, edge) in graph.edges.iter().enumerate() {
+            for node in &edge.linestring {
+                vertices.push(Node::new(index as u64, node.lon, node.lat));
+            }
+            for (a, b) in edge.linestring.iter().tuple_windows() {
+                let (length, azimuth, _, _) = geod.inverse(a.lat, a.lon, b.lat, b.lon);
+                let pieces = (length / INDEX_SPACING).ceil() as usize;
+                for piece in 1..pieces {
+                    let (lat, lon) = geod.direct(a.lat, a.lon, azimuth, length * piece as f64 / pieces as f64);
+                    vertices.push(Node::new(index as u64, lon, lat));
+                }
+            }
+        }
+        let tree = vpsearch::Tree::new(&vertices);
+        Self {
+            vertices,
+            tree,
+        }
+    }
+
+    //nearest edge accepted by `filter` within max_distance metres of the point
+    pub fn snap<F>(&self, graph: &Graph, lon: f64, lat: f64, max_distance: f64, filter: F) -> Option<Snap> where F: Fn(&Edge) -> bool {
+        // a point within max_distance of a segment is within max_distance + half the spacing of an index point on it,
+        // so searching with that radius never misses a candidate edge
+        let needle = Node::new(0, lon, lat);
+        let ids = self.tree.find_nearest_custom(&needle, &(), RadiusBasedNeighborhood::new(max_distance + INDEX_SPACING / 2.0));
+        let candidates: Vec<usize> = ids.into_iter().map(|i| self.vertices[i].id as usize).sorted().dedup().collect();
+        let mut best: Option<Snap> = None;
+        for index in candidates {
+            let edge = &graph.edges[index];
+            if !filter(edge) {
+                continue;
+            }
+            if let Some(snap) = project_edge(index, edge, lon, lat) {
+                if snap.distance <= max_distance && best.is_none_or(|b| snap.distance < b.distance) {
+                    best = Some(snap);
+                }
+            }
+        }
+        best
+    }
+}
+
+//closest point of the edge linestring to (lon, lat)
+pub fn project_edge(index: usize, edge: &Edge, lon: f64, lat: f64) -> Option<Snap> {
+    let geod = Geodesic::wgs84();
+    let mut best: Option<Snap> = None;
+    let mut travelled = 0.0;
+    for (a, b) in edge.linestring.iter().tuple_windows() {
+        let (s_ab, azi_ab, _, _) = geod.inverse(a.lat, a.lon, b.lat, b.lon);
+        // a local equirectangular frame is plenty to find the foot of the perpendicular on street-length segments,
+        // the point itself is then placed on the geodesic
+        let scale = a.lat.to_radians().cos();
+        let (bx, by) = ((b.lon - a.lon) * scale, b.lat - a.lat);
+        let (px, py) = ((lon - a.lon) * scale, lat - a.lat);
+        let squared = bx * bx + by * by;
+        let t = if squared > 0.0 { ((px * bx + py * by) / squared).clamp(0.0, 1.0) } else { 0.0 };
+        let (snap_lat, snap_lon) = geod.direct(a.lat, a.lon, azi_ab, t * s_ab);
+        let distance: f64 = geod.inverse(lat, lon, snap_lat, snap_lon);
+        if best.is_none_or(|b| distance < b.distance) {
+            best = Some(Snap {
+                edge: index,
+                lon: snap_lon,
+                lat: snap_lat,
+                offset: travelled + t * s_ab,
+                distance,
+            });
+        }
+        travelled += s_ab;
+    }
+    best
+}
+
+//geodesic length of a linestring in metres
+pub fn linestring_length(linestring: &[Node]) -> f64 {
+    let geod = Geodesic::wgs84();
+    linestring.iter().tuple_windows().map(|(a, b)| -> f64 { geod.inverse(a.lat, a.lon, b.lat, b.lon) }).sum()
+}
+
+//part of a linestring between two distances in metres from its start
+pub fn cut_linestring(linestring: &[Node], start: f64, end: f64) -> Vec<Node> {
+    let geod = Geodesic::wgs84();
+    let mut cut: Vec<Node> = Vec::new();
+    let mut travelled = 0.0;
+    for (a, b) in linestring.iter().tuple_windows() {
+        let (s_ab, azi_ab, _, _) = geod.inverse(a.lat, a.lon, b.lat, b.lon);
+        let at = |distance: f64| -> Node {
+            let (lat, lon) = geod.direct(a.lat, a.lon, azi_ab, distance - travelled);
+            Node::new(a.id, lon, lat)
+        };
+        if cut.is_empty() && start <= travelled + s_ab {
+            cut.push(at(start.max(travelled)));
+        }
+        if !cut.is_empty() {
+            if end <= travelled + s_ab {
+                cut.push(at(end));
+                return cut;
+            }
+            cut.push(*b);
+        }
+        travelled += s_ab;
+    }
+    cut
+}
+
+#[cfg(test)]
+mod tests {
+    use super::EdgeSnapper;
+    use crate::graph::fixtures::paths;
+    use crate::graph::{Graph, Node};
+    use crate::routing::{Profile, Router};
+    use approx::assert_relative_eq;
+
+    #[test]
+    fn test_snap_onto_vertex() {
+        let graph = Graph::from_csv("testedges.csv", "testnodes.csv");
+        let snapper = EdgeSnapper::new(&graph);
+        let snap = snapper.snap(&graph, -119.0343115, 33.4837658, 50.0, |edge| edge.foot).unwrap();
+        assert_relative_eq!(snap.distance, 0.0, epsilon = 1e-3);
+        let edge = &graph.edges[snap.edge];
+        assert!(edge.source == "2729462058" || edge.target == "2729462058");
+    }
+
+    #[test]
+    fn test_route_between_snaps() {
+        let graph = Graph::from_csv("testedges.csv", "testnodes.csv");
+        let router = Router::new(&graph, Profile::Foot);
+        let snapper = EdgeSnapper::new(&graph);
+        let a = snapper.snap(&graph, -119.0380069, 33.4669861, 50.0, |edge| edge.foot).unwrap();
+        let b = snapper.snap(&graph, -119.0303436, 33.4875751, 50.0, |edge| edge.foot).unwrap();
+        let route = a.route_to(&b, &graph, &router).unwrap();
+        let through_nodes = router.route(2729443585, 2729463686).unwrap();
+        assert_relative_eq!(route.duration, through_nodes.duration, epsilon = 1e-3);
+        assert_relative_eq!(route.distance, through_nodes.distance, epsilon = 1e-3);
+        let (first, last) = (route.geometry.first().unwrap(), route.geometry.last().unwrap());
+        assert!(route.geometry[1].lon != first.lon || route.geometry[1].lat != first.lat);
+        assert_relative_eq!(first.lon, a.lon, epsilon = 1e-7);
+        assert_relative_eq!(last.lat, b.lat, epsilon = 1e-7);
+
+        // both points on one edge are joined directly
+        let back = b.route_to(&b, &graph, &router).unwrap();
+        assert_relative_eq!(back.duration, 0.0, epsilon = 1e-6);
+        assert!(back.route.edges.is_empty());
+    }
+
+    #[test]
+    fn test_snap_respects_radius_and_filter() {
+        let graph = Graph::from_csv("testedges.csv", "testnodes.csv");
+        let snapper = EdgeSnapper::new(&graph);
+        // roughly 110 m north of the node above
+        assert!(snapper.snap(&graph, -119.0343115, 33.4847658, 30.0, |edge| edge.foot).is_none());
+        assert!(snapper.snap(&graph, -119.0343115, 33.4847658, 500.0, |edge| edge.foot).is_some());
+        assert!(snapper.snap(&graph, -119.0343115, 33.4837658, 50.0, |edge| edge.car_forward != "Forbidden").is_none());
+    }
+
+    #[test]
+    fn test_snap_onto_long_segment() {
+        // one straight 2.2 km segment and a short one nearby, the point 30 m off the middle of the long one
+        let nodes = [Node::new(1, -118.0, 34.0), Node::new(2, -118.0, 34.02), Node::new(3, -118.01, 34.01), Node::new(4, -118.0101, 34.01)];
+        let graph = paths(&nodes, &[("long", 0, 1, 2218.0), ("short", 2, 3, 9.0)]);
+        let snapper = EdgeSnapper::new(&graph);
+        let snap = snapper.snap(&graph, -117.99967, 34.01, 50.0, |edge| edge.foot).unwrap();
+        assert_eq!(graph.edges[snap.edge].id, "long");
+        assert_relative_eq!(snap.distance, 30.0, epsilon = 1.0);
+        assert_relative_eq!(snap.offset, 1109.0, epsilon = 5.0);
+        assert!(snapper.snap(&graph, -117.99967, 34.01, 20.0, |edge| edge.foot).is_none());
+    }
+}
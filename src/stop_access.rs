@@ -0,0 +1,31 @@
+use std::time::Instant;
+mod graph;
+mod calendar;
+mod transfers;
+mod geodesic;
+mod interner;
+mod routing;
+mod snap;
+mod access;
+use graph::{Graph, GTFSGraph};
+use access::StopAccess;
+
+fn main() {
+    let args = arguments::parse(std::env::args()).unwrap();
+    let edges = args.get::<String>("edges").unwrap_or_else(|| "edges.csv".to_string());
+    let nodes = args.get::<String>("nodes").unwrap_or_else(|| "nodes.csv".to_string());
+    let max_distance = args.get::<f64>("max_distance").unwrap_or(200.0);
+
+    let start_time = Instant::now();
+    let graph = Graph::from_csv(&edges, &nodes);
+    eprintln!("from_csv took {:?}", start_time.elapsed().as_secs_f64());
+    let start_time = Instant::now();
+    let gtfs_graph = GTFSGraph::from_file("gtfs_rail.zip", "f-9q5-metro~losangeles~rail");
+    eprintln!("GTFSGraph took {:?}", start_time.elapsed().as_secs_f64());
+
+    let start_time = Instant::now();
+    let access = StopAccess::link(&gtfs_graph, &graph, max_distance);
+    eprintln!("linked {} stops, {} unlinked, took {:?}", access.links.len(), access.unlinked.len(), start_time.elapsed().as_secs_f64());
+    access.to_csv("stop_links.csv").unwrap();
+    access.unlinked_to_csv("unlinked_stops.csv").unwrap();
+}
@@ -1,6 +1,10 @@
 use std::time::Instant;
 
 mod graph;
+mod calendar;
+mod transfers;
+mod geodesic;
+mod interner;
 use graph::{Graph, GTFSGraph};
 
 fn main() {
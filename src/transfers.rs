@@ -0,0 +1,291 @@
+use std::{cmp::Reverse, collections::{BinaryHeap, HashMap}, error::Error, fs::{self, File}, io::{ErrorKind, Read}, path::Path};
+use csv::{ReaderBuilder, Trim};
+use serde::{de::DeserializeOwned, Serialize, Deserialize};
+use crate::graph::TRANSFER_WALK_SPEED;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TransferType {
+    //0 or empty
+    Recommended,
+    //1, the departing vehicle waits for the arriving one
+    Timed,
+    //2, needs min_transfer_time
+    MinimumTime,
+    //3
+    NotPossible,
+    //4, stay on board between trips
+    InSeat,
+    //5, alight and board again between trips
+    ReBoard,
+}
+
+impl TransferType {
+    pub fn from_code(code: Option<u8>) -> Self {
+        match code {
+            Some(1) => TransferType::Timed,
+            Some(2) => TransferType::MinimumTime,
+            Some(3) => TransferType::NotPossible,
+            Some(4) => TransferType::InSeat,
+            Some(5) => TransferType::ReBoard,
+            _ => TransferType::Recommended,
+        }
+    }
+}
+
+//transfers.txt row, unset ids match anything
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct TransferRule {
+    pub from_stop: Option<String>,
+    pub to_stop: Option<String>,
+    pub from_route: Option<String>,
+    pub to_route: Option<String>,
+    pub from_trip: Option<String>,
+    pub to_trip: Option<String>,
+    pub transfer_type: TransferType,
+    pub min_transfer_time: Option<u32>,
+}
+
+impl TransferRule {
+    // trips outrank routes, which outrank stops alone
+    fn specificity(&self) -> u32 {
+        let side = |route: &Option<String>, trip: &Option<String>| if trip.is_some() { 2 } else if route.is_some() { 1 } else { 0 };
+        side(&self.from_route, &self.from_trip) + side(&self.to_route, &self.to_trip)
+    }
+
+    fn matches(&self, from_stop: &str, to_stop: &str, from: (&str, &str), to: (&str, &str)) -> bool {
+        let matching = |rule: &Option<String>, id: &str| rule.as_deref().is_none_or(|rule| rule == id);
+        matching(&self.from_stop, from_stop) && matching(&self.to_stop, to_stop)
+            && matching(&self.from_route, from.0) && matching(&self.from_trip, from.1)
+            && matching(&self.to_route, to.0) && matching(&self.to_trip, to.1)
+    }
+
+    //only for some routes or trips
+    pub fn names_trips(&self) -> bool {
+        self.specificity() > 0
+    }
+
+    //between stops, whoever is riding
+    pub fn is_stop_level(&self) -> bool {
+        self.from_stop.is_some() && self.to_stop.is_some() && self.specificity() == 0
+    }
+}
+
+//pathways.txt row
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Pathway {
+    pub id: String,
+    pub from_stop: String,
+    pub to_stop: String,
+    //1 walkway, 2 stairs, 3 moving sidewalk, 4 escalator, 5 elevator, 6 fare gate, 7 exit gate
+    pub mode: u8,
+    pub bidirectional: bool,
+    //metres
+    pub length: Option<f64>,
+    //seconds
+    pub traversal_time: Option<u32>,
+    pub stair_count: Option<i32>,
+}
+
+impl Pathway {
+    //traversal_time when the feed has it, else estimated from the length, the stairs or the mode
+    pub fn seconds(&self) -> u32 {
+        if let Some(seconds) = self.traversal_time {
+            return seconds;
+        }
+        if let Some(length) = self.length {
+            return (length / TRANSFER_WALK_SPEED).ceil() as u32;
+        }
+        if let Some(stairs) = self.stair_count {
+            return stairs.unsigned_abs();
+        }
+        match self.mode {
+            5 => 60,
+            6 | 7 => 10,
+            _ => 30,
+        }
+    }
+}
+
+//levels.txt row
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Level {
+    pub id: String,
+    //0 at street level, negative below
+    pub index: f64,
+    pub name: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct TransferRow {
+    from_stop_id: Option<String>,
+    to_stop_id: Option<String>,
+    from_route_id: Option<String>,
+    to_route_id: Option<String>,
+    from_trip_id: Option<String>,
+    to_trip_id: Option<String>,
+    transfer_type: Option<u8>,
+    min_transfer_time: Option<u32>,
+}
+
+#[derive(Deserialize)]
+struct PathwayRow {
+    pathway_id: String,
+    from_stop_id: String,
+    to_stop_id: String,
+    pathway_mode: u8,
+    is_bidirectional: u8,
+    length: Option<f64>,
+    traversal_time: Option<u32>,
+    stair_count: Option<i32>,
+}
+
+#[derive(Deserialize)]
+struct LevelRow {
+    level_id: String,
+    level_index: f64,
+    level_name: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct StationRules {
+    pub transfers: Vec<TransferRule>,
+    pub pathways: Vec<Pathway>,
+    //<level id, level>
+    pub levels: HashMap<String, Level>,
+}
+
+impl StationRules {
+    //transfers.txt, pathways.txt and levels.txt from a feed zip or directory, each may be missing
+    pub fn from_file(file: &str) -> Result<Self, Box<dyn Error>> {
+        let transfers = read_rows::<TransferRow>(file, "transfers.txt")?.into_iter().map(|row| TransferRule {
+            from_stop: row.from_stop_id,
+            to_stop: row.to_stop_id,
+            from_route: row.from_route_id,
+            to_route: row.to_route_id,
+            from_trip: row.from_trip_id,
+            to_trip: row.to_trip_id,
+            transfer_type: TransferType::from_code(row.transfer_type),
+            min_transfer_time: row.min_transfer_time,
+        }).collect();
+        let pathways = read_rows::<PathwayRow>(file, "pathways.txt")?.into_iter().map(|row| Pathway {
+            id: row.pathway_id,
+            from_stop: row.from_stop_id,
+            to_stop: row.to_stop_id,
+            mode: row.pathway_mode,
+            bidirectional: row.is_bidirectional == 1,
+            length: row.length,
+            traversal_time: row.traversal_time,
+            stair_count: row.stair_count,
+        }).collect();
+        let levels = read_rows::<LevelRow>(file, "levels.txt")?.into_iter()
+            .map(|row| (row.level_id.clone(), Level { id: row.level_id, index: row.level_index, name: row.level_name }))
+            .collect();
+        Ok(Self { transfers, pathways, levels })
+    }
+
+    //the most specific rule for changing from one (route, trip) to another between two stops
+    pub fn rule(&self, from_stop: &str, to_stop: &str, from: (&str, &str), to: (&str, &str)) -> Option<&TransferRule> {
+        self.transfers.iter()
+            .filter(|rule| rule.matches(from_stop, to_stop, from, to))
+            .max_by_key(|rule| rule.specificity())
+    }
+
+    //<(from stop, to stop), seconds> fastest way through the pathway network between every pair of its nodes
+    pub fn pathway_times(&self) -> HashMap<(String, String), u32> {
+        let mut arcs: HashMap<&str, Vec<(&str, u32)>> = HashMap::new();
+        for pathway in &self.pathways {
+            arcs.entry(&pathway.from_stop).or_default().push((&pathway.to_stop, pathway.seconds()));
+            if pathway.bidirectional {
+                arcs.entry(&pathway.to_stop).or_default().push((&pathway.from_stop, pathway.seconds()));
+            }
+        }
+        let mut times = HashMap::new();
+        for source in arcs.keys() {
+            let mut best: HashMap<&str, u32> = HashMap::from([(*source, 0)]);
+            let mut heap = BinaryHeap::from([Reverse((0, *source))]);
+            while let Some(Reverse((seconds, node))) = heap.pop() {
+                if seconds > best[node] {
+                    continue;
+                }
+                for (next, cost) in arcs.get(node).into_iter().flatten() {
+                    if best.get(next).is_none_or(|known| seconds + cost < *known) {
+                        best.insert(next, seconds + cost);
+                        heap.push(Reverse((seconds + cost, *next)));
+                    }
+                }
+            }
+            for (target, seconds) in best {
+                if target != *source {
+                    times.insert((source.to_string(), target.to_string()), seconds);
+                }
+            }
+        }
+        times
+    }
+}
+
+//rows of one optional file in a feed zip or unzipped directory, none if the feed doesn't have it
+fn read_rows<T: DeserializeOwned>(file: &str, name: &str) -> Result<Vec<T>, Box<dyn Error>> {
+    let path = Path::new(file);
+    let data = if path.is_dir() {
+        match fs::read(path.join(name)) {
+            Ok(data) => data,
+            Err(error) if error.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(error) => return Err(error.into()),
+        }
+    } else {
+        let mut archive = zip::ZipArchive::new(File::open(path)?)?;
+        // some feeds keep their files in a folder inside the zip
+        let index = (0..archive.len()).find(|index| {
+            archive.by_index(*index).map(|entry| entry.name().rsplit('/').next() == Some(name)).unwrap_or(false)
+        });
+        let Some(index) = index else {
+            return Ok(Vec::new());
+        };
+        let mut data = Vec::new();
+        archive.by_index(index)?.read_to_end(&mut data)?;
+        data
+    };
+    let mut rdr = ReaderBuilder::new().flexible(true).trim(Trim::All).from_reader(data.as_slice());
+    Ok(rdr.deserialize().collect::<Result<Vec<T>, _>>()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use super::{StationRules, TransferType};
+
+    #[test]
+    fn test_read_rules_and_pathways() {
+        let dir = std::env::temp_dir().join("algo_test_station_rules");
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("transfers.txt"), "from_stop_id,to_stop_id,from_route_id,to_route_id,from_trip_id,to_trip_id,transfer_type,min_transfer_time\n\
+            A,A,,,,,2,180\n\
+            A,B,,,,,3,\n\
+            A,A,,,t1,t2,1,\n\
+            A,A,red,,,,2,300\n").unwrap();
+        fs::write(dir.join("pathways.txt"), "pathway_id,from_stop_id,to_stop_id,pathway_mode,is_bidirectional,length,traversal_time,stair_count\n\
+            p1,A,hall,1,1,70,,\n\
+            p2,hall,C,2,0,,,40\n\
+            p3,C,A,4,0,,25,\n").unwrap();
+        fs::write(dir.join("levels.txt"), "level_id,level_index,level_name\nstreet,0,Street\nplatform,-1,\n").unwrap();
+        let rules = StationRules::from_file(dir.to_str().unwrap()).unwrap();
+        assert_eq!(rules.transfers.len(), 4);
+        assert_eq!(rules.levels["platform"].index, -1.0);
+        assert!(rules.levels["platform"].name.is_none());
+
+        // the trip pair beats the route rule, which beats the stop rule
+        assert_eq!(rules.rule("A", "A", ("red", "t1"), ("blue", "t2")).unwrap().transfer_type, TransferType::Timed);
+        assert_eq!(rules.rule("A", "A", ("red", "t1"), ("blue", "t3")).unwrap().min_transfer_time, Some(300));
+        assert_eq!(rules.rule("A", "A", ("green", "t9"), ("blue", "t3")).unwrap().min_transfer_time, Some(180));
+        assert!(rules.rule("A", "C", ("green", "t9"), ("blue", "t3")).is_none());
+        assert!(rules.transfers[1].is_stop_level());
+
+        let times = rules.pathway_times();
+        // 50 s walkway one way or the other, 40 stairs down to C, 25 s escalator back up
+        assert_eq!(times[&("A".to_string(), "C".to_string())], 90);
+        assert_eq!(times[&("C".to_string(), "A".to_string())], 25);
+        assert_eq!(times[&("C".to_string(), "hall".to_string())], 75);
+        assert!(!times.contains_key(&("A".to_string(), "A".to_string())));
+    }
+}
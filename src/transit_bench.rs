@@ -0,0 +1,86 @@
+use std::time::Instant;
+use chrono::NaiveDate;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+mod graph;
+mod calendar;
+mod transfers;
+mod geodesic;
+mod interner;
+mod raptor;
+mod csa;
+use graph::{format_time, GTFSGraph};
+use raptor::Raptor;
+use csa::Csa;
+
+fn main() {
+    let args = arguments::parse(std::env::args()).unwrap();
+    let feed = args.get::<String>("feed").unwrap_or_else(|| "gtfs_rail.zip".to_string());
+    let queries = args.get::<usize>("queries").unwrap_or(1000);
+    let departure = args.get::<u32>("departure").unwrap_or(8 * 3600);
+    let max_transfers = args.get::<usize>("transfers").unwrap_or(5);
+
+    let start_time = Instant::now();
+    //YYYY-MM-DD service day, today in the agency's timezone if not given
+    let gtfs = match args.get::<String>("date").and_then(|date| NaiveDate::parse_from_str(&date, "%Y-%m-%d").ok()) {
+        Some(date) => GTFSGraph::from_file_between(&feed, "f-9q5-metro~losangeles~rail", date, date),
+        None => GTFSGraph::from_file(&feed, "f-9q5-metro~losangeles~rail"),
+    };
+    eprintln!("from_file took {:?}", start_time.elapsed().as_secs_f64());
+    let start_time = Instant::now();
+    let raptor = Raptor::new(&gtfs);
+    eprintln!("Raptor::new took {:?}, {} patterns", start_time.elapsed().as_secs_f64(), raptor.patterns.len());
+    let start_time = Instant::now();
+    let csa = Csa::new(&gtfs);
+    eprintln!("Csa::new took {:?}, {} connections", start_time.elapsed().as_secs_f64(), csa.connections.len());
+    if csa.stops.is_empty() {
+        println!("No trips in service");
+        return;
+    }
+
+    let mut rng = StdRng::seed_from_u64(0);
+    let pairs: Vec<(&String, &String)> = (0..queries)
+        .map(|_| (&csa.stops[rng.gen_range(0..csa.stops.len())], &csa.stops[rng.gen_range(0..csa.stops.len())]))
+        .collect();
+
+    let start_time = Instant::now();
+    let raptor_arrivals: Vec<Option<u32>> = pairs.iter()
+        .map(|(origin, destination)| raptor.earliest_arrival(origin, destination, departure, max_transfers).map(|journey| journey.arrival))
+        .collect();
+    let raptor_time = start_time.elapsed().as_secs_f64();
+    let start_time = Instant::now();
+    let csa_arrivals: Vec<Option<u32>> = pairs.iter()
+        .map(|(origin, destination)| csa.earliest_arrival(origin, destination, departure).map(|journey| journey.arrival))
+        .collect();
+    let csa_time = start_time.elapsed().as_secs_f64();
+
+    // raptor is capped at max_transfers, so it can only be later
+    let differing = raptor_arrivals.iter().zip(&csa_arrivals).filter(|(raptor, csa)| raptor != csa).count();
+    println!("{} queries from {}: raptor {:.6} s/query, csa {:.6} s/query, {} differ", queries, format_time(departure), raptor_time / queries as f64, csa_time / queries as f64, differing);
+
+    // the same window through both range queries
+    let window = 3600;
+    let ranged = &pairs[..pairs.len().min(100)];
+    let start_time = Instant::now();
+    let profiles: Vec<Vec<(u32, u32)>> = ranged.iter()
+        .map(|(origin, destination)| csa.profile(origin, destination, departure, departure + window).iter().map(|entry| (entry.departure, entry.arrival)).collect())
+        .collect();
+    let profile_time = start_time.elapsed().as_secs_f64();
+    let start_time = Instant::now();
+    for (origin, destination) in ranged {
+        raptor.range(origin, destination, departure, departure + window, max_transfers);
+    }
+    let range_time = start_time.elapsed().as_secs_f64();
+
+    // csa's profile also drops departures beaten by ones after the window, so check raptor against it over the rest of the day
+    let differing = ranged.iter().zip(&profiles).filter(|((origin, destination), profile)| {
+        let journeys = raptor.range(origin, destination, departure, 48 * 3600, max_transfers);
+        let mut times: Vec<(u32, u32)> = journeys.iter()
+            .filter(|journey| journey.departure <= departure + window)
+            .filter(|journey| !journeys.iter().any(|other| other.departure >= journey.departure && other.arrival <= journey.arrival && (other.departure, other.arrival) != (journey.departure, journey.arrival)))
+            .map(|journey| (journey.departure, journey.arrival))
+            .collect();
+        times.dedup();
+        times != **profile
+    }).count();
+    println!("{} range queries over {} s: raptor {:.6} s/query, csa {:.6} s/query, {} differ", ranged.len(), window, range_time / ranged.len() as f64, profile_time / ranged.len() as f64, differing);
+}
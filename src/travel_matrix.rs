@@ -0,0 +1,41 @@
+use std::{fs, time::Instant};
+mod graph;
+mod calendar;
+mod transfers;
+mod geodesic;
+mod interner;
+mod routing;
+mod snap;
+mod matrix;
+use graph::Graph;
+use routing::{Profile, Router};
+use snap::EdgeSnapper;
+use matrix::{read_locations, Matrix};
+
+fn main() {
+    let args = arguments::parse(std::env::args()).unwrap();
+    let edges = args.get::<String>("edges").unwrap_or_else(|| "edges.csv".to_string());
+    let nodes = args.get::<String>("nodes").unwrap_or_else(|| "nodes.csv".to_string());
+    let profile = args.get::<Profile>("profile").unwrap_or(Profile::Foot);
+    let sources = args.get::<String>("sources").expect("Add --sources <csv of id,lon,lat>");
+    let targets = args.get::<String>("targets").unwrap_or_else(|| sources.clone());
+    let output = args.get::<String>("output").unwrap_or_else(|| "matrix.csv".to_string());
+
+    let start_time = Instant::now();
+    let graph = Graph::from_csv(&edges, &nodes);
+    let router = Router::new(&graph, profile);
+    let snapper = EdgeSnapper::new(&graph);
+    eprintln!("loading took {:?}", start_time.elapsed().as_secs_f64());
+
+    let sources = read_locations(&sources).unwrap();
+    let targets = read_locations(&targets).unwrap();
+    let start_time = Instant::now();
+    let matrix = Matrix::new(&graph, &router, &snapper, &sources, &targets, 500.0);
+    eprintln!("{}x{} matrix took {:?}", sources.len(), targets.len(), start_time.elapsed().as_secs_f64());
+
+    if output.ends_with(".json") {
+        fs::write(&output, matrix.to_json().to_string()).unwrap();
+    } else {
+        matrix.to_csv(&output).unwrap();
+    }
+}
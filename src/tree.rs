@@ -0,0 +1,125 @@
+use std::error::Error;
+use csv::Writer;
+use serde::Serialize;
+use serde_json::{json, Value};
+use crate::graph::{Graph, Node};
+use crate::routing::Router;
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct TreeNode {
+    //osm node id
+    pub node: u64,
+    pub lon: f64,
+    pub lat: f64,
+    //metres along the tree from the origin
+    pub distance: f64,
+    //seconds along the tree at the profile's speeds
+    pub duration: f64,
+    //Graph edge id reaching this node, empty at the origin
+    pub edge: Option<String>,
+    //osm node id at the other end of that edge
+    pub parent: Option<u64>,
+    #[serde(skip)]
+    //edge linestring in travel order, empty at the origin
+    pub geometry: Vec<Node>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ShortestPathTree {
+    pub origin: u64,
+    //reached nodes, nearest first
+    pub nodes: Vec<TreeNode>,
+}
+
+impl ShortestPathTree {
+    //one to all search by distance over the edges the router's profile may use, stopping past cutoff metres
+    pub fn new(graph: &Graph, router: &Router, origin: usize, cutoff: Option<f64>) -> Self {
+        let limit = cutoff.unwrap_or(f64::INFINITY);
+        let search = router.dijkstra(&[(origin, 0.0)], None, limit, |arc| arc.length);
+        let mut duration = vec![0.0; router.node_count()];
+        let mut order: Vec<usize> = (0..router.node_count()).filter(|node| search.cost[*node] <= limit).collect();
+        order.sort_by(|a, b| search.cost[*a].total_cmp(&search.cost[*b]));
+
+        let mut nodes = Vec::new();
+        for node in order {
+            let (lon, lat) = router.coords[node];
+            let mut tree_node = TreeNode { node: router.nodes.id(node), lon, lat, distance: search.cost[node], duration: 0.0, edge: None, parent: None, geometry: Vec::new() };
+            if let Some((tail, arc)) = search.parent[node] {
+                // parents are nearer, so already have their duration
+                duration[node] = duration[tail] + arc.duration;
+                let edge = &graph.edges[arc.edge];
+                let mut geometry = edge.linestring.clone();
+                if !arc.forward {
+                    geometry.reverse();
+                }
+                tree_node.duration = duration[node];
+                tree_node.edge = Some(edge.id.clone());
+                tree_node.parent = Some(router.nodes.id(tail));
+                tree_node.geometry = geometry;
+            }
+            nodes.push(tree_node);
+        }
+        Self { origin: router.nodes.id(origin), nodes }
+    }
+
+    pub fn to_csv(&self, file_path: &str) -> Result<(), Box<dyn Error>> {
+        let mut wtr = Writer::from_path(file_path)?;
+        for node in &self.nodes {
+            wtr.serialize(node)?;
+        }
+        wtr.flush()?;
+        Ok(())
+    }
+
+    //the origin as a point, every other node as the linestring of the edge that reached it
+    pub fn to_geojson(&self) -> Value {
+        let features: Vec<Value> = self.nodes.iter().map(|node| {
+            let geometry = if node.geometry.is_empty() {
+                json!({ "type": "Point", "coordinates": [node.lon, node.lat] })
+            } else {
+                let coordinates: Vec<[f64; 2]> = node.geometry.iter().map(|point| [point.lon, point.lat]).collect();
+                json!({ "type": "LineString", "coordinates": coordinates })
+            };
+            json!({
+                "type": "Feature",
+                "properties": { "node": node.node, "distance": node.distance, "duration": node.duration, "edge": node.edge, "parent": node.parent },
+                "geometry": geometry,
+            })
+        }).collect();
+        json!({ "type": "FeatureCollection", "features": features })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ShortestPathTree;
+    use crate::graph::Graph;
+    use crate::routing::{Profile, Router};
+    use approx::assert_relative_eq;
+
+    #[test]
+    fn test_tree_and_cutoff() {
+        let graph = Graph::from_csv("testedges.csv", "testnodes.csv");
+        let router = Router::new(&graph, Profile::Foot);
+        let origin = router.node_index(2729443585).unwrap();
+        let tree = ShortestPathTree::new(&graph, &router, origin, None);
+        assert_eq!(tree.nodes[0].node, 2729443585);
+        assert!(tree.nodes[0].edge.is_none());
+        assert!(tree.nodes.windows(2).all(|pair| pair[0].distance <= pair[1].distance));
+        // every predecessor is in the tree and nearer
+        for node in &tree.nodes[1..] {
+            let parent = tree.nodes.iter().find(|other| Some(other.node) == node.parent).unwrap();
+            assert!(parent.distance < node.distance);
+            let last = node.geometry.last().unwrap();
+            assert_relative_eq!(last.lon, node.lon, epsilon = 1e-7);
+        }
+        let target = tree.nodes.iter().find(|node| node.node == 2729463686).unwrap();
+        let route = router.route(2729443585, 2729463686).unwrap();
+        assert_relative_eq!(target.distance, route.distance, epsilon = 1e-6);
+
+        let cut = ShortestPathTree::new(&graph, &router, origin, Some(1000.0));
+        assert!(cut.nodes.len() < tree.nodes.len());
+        assert!(cut.nodes.iter().all(|node| node.distance <= 1000.0));
+        assert_eq!(cut.to_geojson()["features"].as_array().unwrap().len(), cut.nodes.len());
+    }
+}